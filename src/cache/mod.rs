@@ -1,5 +1,6 @@
 use std::{cmp, fs};
 use std::fs::remove_file;
+use std::io::ErrorKind;
 use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
 
@@ -7,13 +8,15 @@ use log::error;
 
 use crate::parameters::UrlParameters;
 use crate::services::formats::OutputFormat;
+use crate::services::profiles;
 
 pub mod buster;
 
 pub fn get_path_from_url_parameters(url_parameters: &UrlParameters<'_>, output_format: &OutputFormat) -> String {
 
     let env_cache = std::env::var("CACHE").unwrap_or("/tmp".to_string());
-    let params_hash = crate::crypto::json_hash(url_parameters);
+    // Resolved encoding profile is a part of the key, so that changed profiles are not served from cache
+    let params_hash = crate::crypto::json_hash(&(url_parameters, profiles::get(url_parameters)));
     let filename_hash = crate::crypto::string_hash(&url_parameters.path.to_string_lossy());
 
     let parts = [&params_hash[0..2], &params_hash[2..4], &params_hash[4..6]];
@@ -57,6 +60,7 @@ pub fn is_cached(cache_path: &str, url_parameters: &UrlParameters<'_>) -> bool {
 
     if cached_max_time < original_max_time {
         remove_file(cache_path).unwrap_or_else(|e| error!("Failed to remove cache file: {}", e));
+        remove_quality(cache_path);
         return false;
     }
     
@@ -64,6 +68,24 @@ pub fn is_cached(cache_path: &str, url_parameters: &UrlParameters<'_>) -> bool {
     
 }
 
+// Encoder quality chosen by target quality search, stored next to the cached image with the same file stem
+pub fn get_quality_path(cache_path: &str) -> String {
+    let path = Path::new(cache_path);
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_extension(format!("{extension}-quality")).to_string_lossy().to_string()
+}
+
+// Quality is removed together with the cached image, so that it is searched again for the new original
+fn remove_quality(cache_path: &str) {
+    let quality_path = get_quality_path(cache_path);
+
+    if let Err(e) = remove_file(&quality_path) {
+        if e.kind() != ErrorKind::NotFound {
+            error!("Failed to remove cache quality file: {}", e);
+        }
+    }
+}
+
 pub fn index(cache_path: String, file_path: PathBuf) {
     
     let cache_path = Path::new(&cache_path);
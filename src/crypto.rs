@@ -12,6 +12,10 @@ pub fn string_hash(data: &str) -> String {
     xxh3_64(data.as_bytes()).to_string()
 }
 
+pub fn bytes_hash(data: &[u8]) -> String {
+    xxh3_64(data).to_string()
+}
+
 pub fn json_hash<T: serde::Serialize>(data: &T) -> String {
     xxh3_64(serde_json::to_string(data).unwrap().as_bytes()).to_string()
 }
\ No newline at end of file
@@ -6,7 +6,7 @@ use actix_web::{App, HttpServer};
 use actix_web::middleware::Logger;
 use dotenv::dotenv;
 use libvips::VipsApp;
-use log::LevelFilter;
+use log::{error, LevelFilter};
 use simplelog::{ColorChoice, CombinedLogger, Config, TerminalMode, TermLogger, WriteLogger};
 
 use crate::routes::routes;
@@ -23,6 +23,12 @@ async fn main() -> std::io::Result<()> {
 
     dotenv().ok();
 
+    if env::args().nth(1).as_deref() == Some("doctor") {
+        let _app = VipsApp::new("vips", false).unwrap();
+        services::capabilities::doctor();
+        return Ok(());
+    }
+
     let log_level = match env::var("LOG").unwrap().as_str() {
         "warn" => LevelFilter::Warn,
         "error" => LevelFilter::Error,
@@ -54,6 +60,14 @@ async fn main() -> std::io::Result<()> {
     app.cache_set_max_files(0);
     app.cache_set_max_mem(0);
 
+    services::capabilities::init();
+    services::fonts::init();
+
+    if let Err(e) = services::watermarks::init() {
+        error!("{e}");
+        return Err(std::io::Error::other(e));
+    }
+
     HttpServer::new(|| {
 
         let mut cors = Cors::default()
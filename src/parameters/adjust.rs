@@ -0,0 +1,107 @@
+use serde::Serialize;
+
+const MAX_MULTIPLIER: f64 = 10.0;
+const MIN_GAMMA: f64 = 0.1;
+const MAX_GAMMA: f64 = 10.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub enum Auto {
+    Levels,
+    Normalize
+}
+
+impl Auto {
+    pub fn from(value: &Option<String>) -> Option<Auto> {
+
+        // Format: auto=levels|normalize
+        match value.as_deref() {
+            Some("levels") => Some(Auto::Levels),
+            Some("normalize") | Some("normalise") => Some(Auto::Normalize),
+            _ => None
+        }
+
+    }
+}
+
+// Multipliers 1.0 and hue rotation 0 keep the image unchanged
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub struct Adjustments {
+    pub brightness: f64,
+    pub contrast: f64,
+    pub saturation: f64,
+    pub hue: f64,
+    pub gamma: f64,
+    pub auto: Option<Auto>
+}
+
+impl Default for Adjustments {
+    fn default() -> Self {
+        Self {
+            brightness: 1.0,
+            contrast: 1.0,
+            saturation: 1.0,
+            hue: 0.0,
+            gamma: 1.0,
+            auto: None
+        }
+    }
+}
+
+impl Adjustments {
+    pub fn from(brightness: &Option<String>, contrast: &Option<String>, saturation: &Option<String>, hue: &Option<String>, gamma: &Option<String>, auto: &Option<String>) -> Adjustments {
+
+        // Format: brightness, contrast and saturation as multipliers, hue in degrees, gamma as exponent
+        Adjustments {
+            brightness: parse_multiplier(brightness).unwrap_or(1.0),
+            contrast: parse_multiplier(contrast).unwrap_or(1.0),
+            saturation: parse_multiplier(saturation).unwrap_or(1.0),
+            hue: parse_number(hue).map(|hue| hue.rem_euclid(360.0)).unwrap_or(0.0),
+            gamma: parse_number(gamma).filter(|gamma| *gamma > 0.0).map(|gamma| gamma.clamp(MIN_GAMMA, MAX_GAMMA)).unwrap_or(1.0),
+            auto: Auto::from(auto)
+        }
+
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Adjustments::default()
+    }
+
+    pub fn modulates(&self) -> bool {
+        self.brightness != 1.0 || self.contrast != 1.0 || self.saturation != 1.0 || self.hue != 0.0
+    }
+}
+
+fn parse_number(value: &Option<String>) -> Option<f64> {
+    let value = value.as_ref()?.trim();
+    value.strip_suffix("deg").unwrap_or(value).parse::<f64>().ok().filter(|value| value.is_finite())
+}
+
+fn parse_multiplier(value: &Option<String>) -> Option<f64> {
+    parse_number(value).filter(|value| *value >= 0.0).map(|value| value.min(MAX_MULTIPLIER))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+
+    #[test]
+    fn test_adjustments_from() {
+        assert!(Adjustments::from(&None, &None, &None, &None, &None, &None).is_none());
+        assert!(Adjustments::from(&value("1"), &value("1.0"), &value("invalid"), &value("360"), &value("0"), &value("invalid")).is_none());
+
+        let adjustments = Adjustments::from(&value("1.2"), &value("0.8"), &value("50"), &value("-90deg"), &value("2.2"), &value("levels"));
+        assert_eq!(adjustments.brightness, 1.2);
+        assert_eq!(adjustments.contrast, 0.8);
+        assert_eq!(adjustments.saturation, 10.0);
+        assert_eq!(adjustments.hue, 270.0);
+        assert_eq!(adjustments.gamma, 2.2);
+        assert_eq!(adjustments.auto, Some(Auto::Levels));
+        assert!(adjustments.modulates());
+
+        assert_eq!(Adjustments::from(&value("-1"), &None, &None, &None, &value("100"), &value("normalize")), Adjustments { gamma: 10.0, auto: Some(Auto::Normalize), ..Adjustments::default() });
+    }
+}
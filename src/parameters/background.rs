@@ -1,133 +1,78 @@
 use log::error;
 use serde::Serialize;
+use crate::parameters::color::Color;
 
-const COLORS: [&str; 3] = ["transparent", "black", "white"];
+const DEFAULT_GRADIENT_ANGLE: f64 = 180.0; // deg, top to bottom
+const DEFAULT_AMBIENT_SIGMA: f64 = 20.0;
+const MAX_AMBIENT_SIGMA: f64 = 100.0;
 
-#[derive(Default, Clone, Debug, PartialEq, Serialize)]
-pub struct Background(pub u8, pub u8, pub u8, pub u8);
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Background {
+    Color(Color),
+    Linear { from: Color, to: Color, angle: f64 },
+    Radial { inner: Color, outer: Color },
+    Ambient { sigma: f64 }
+}
 
 impl Background {
-    pub fn is_transparent(&self) -> bool {
-        self.3 == 0
-    }
-    
     pub fn from(value: &Option<String>) -> Option<Background> {
 
-        // Format: bg=#rrggbb[aa] or bg=r,g,b[,a] or bg=transparent
+        // Format: bg=<color> or bg=linear:<from>:<to>[:<angle>] or bg=radial:<inner>:<outer> or bg=ambient[:<sigma>]
         let value = match value {
-            Some(value) => value,
+            Some(value) => value.trim().to_lowercase(),
             None => return None
         };
 
-        if COLORS.contains(&value.as_str()) {
-            return match value.as_str() {
-                "transparent" => Some(Background(0, 0, 0, 0)),
-                "black" => Some(Background(0, 0, 0, 255)),
-                "white" => Some(Background(255, 255, 255, 255)),
-                _ => None
-            };
-        }
+        let parts: Vec<&str> = value.split(':').collect();
 
-        match value.contains(',') {
-            false => {
-                if value.len() != 6 && value.len() != 8 {
-                    error!("Invalid background format: {value}");
-                    return None;
-                }
-                
-                let r = match u8::from_str_radix(&value[0..2], 16) {
-                    Ok(value) => value,
-                    Err(_) => {
-                        error!("Invalid background format: {value}");
-                        return None
-                    }
+        let background = match parts[0] {
+            "linear" if parts.len() == 3 || parts.len() == 4 => {
+                let angle = match parts.get(3) {
+                    Some(angle) => angle.strip_suffix("deg").unwrap_or(angle).parse::<f64>().ok().map(|angle| angle.rem_euclid(360.0)),
+                    None => Some(DEFAULT_GRADIENT_ANGLE)
                 };
-                
-                let g = match u8::from_str_radix(&value[2..4], 16) {
-                    Ok(value) => value,
-                    Err(_) => {
-                        error!("Invalid background format: {value}");
-                        return None
-                    }
-                };
-                
-                let b = match u8::from_str_radix(&value[4..6], 16) {
-                    Ok(value) => value,
-                    Err(_) => {
-                        error!("Invalid background format: {value}");
-                        return None
-                    }
-                };
-                
-                // alpha is optional
-                let a = if value.len() == 8 {
-                    match u8::from_str_radix(&value[6..8], 16) {
-                        Ok(value) => value,
-                        Err(_) => {
-                            error!("Invalid background format: {value}");
-                            return None
-                        }
-                    }
-                } else {
-                    255
+
+                match (Self::color(parts[1]), Self::color(parts[2]), angle) {
+                    (Some(from), Some(to), Some(angle)) => Some(Background::Linear { from, to, angle }),
+                    _ => None
+                }
+            },
+            "radial" if parts.len() == 3 => {
+                match (Self::color(parts[1]), Self::color(parts[2])) {
+                    (Some(inner), Some(outer)) => Some(Background::Radial { inner, outer }),
+                    _ => None
+                }
+            },
+            "ambient" if parts.len() <= 2 => {
+                let sigma = match parts.get(1) {
+                    Some(sigma) => sigma.parse::<f64>().ok().filter(|sigma| *sigma > 0.0).map(|sigma| sigma.min(MAX_AMBIENT_SIGMA)),
+                    None => Some(DEFAULT_AMBIENT_SIGMA)
                 };
 
-                Some(Background(r, g, b, a))
+                sigma.map(|sigma| Background::Ambient { sigma })
             },
-            true => {
-                let parts: Vec<&str> = value.split(',').collect();
+            "linear" | "radial" | "ambient" => None,
+            _ => return Color::from(&Some(value)).map(Background::Color)
+        };
 
-                if parts.len() < 3 || parts.len() > 4 {
-                    error!("Invalid background format: {value}");
-                    return None;
-                }
+        if background.is_none() {
+            error!("Invalid background format: {value}");
+        }
 
-                let r = match parts[0].parse::<u8>() {
-                    Ok(value) => value,
-                    Err(_) => {
-                        error!("Invalid background format: {value}");
-                        return None
-                    }
-                };
-                
-                let g = match parts[1].parse::<u8>() {
-                    Ok(value) => value,
-                    Err(_) => {
-                        error!("Invalid background format: {value}");
-                        return None
-                    }
-                };
-                
-                let b = match parts[2].parse::<u8>() {
-                    Ok(value) => value,
-                    Err(_) => {
-                        error!("Invalid background format: {value}");
-                        return None
-                    }
-                };
-                
-                let a = if parts.len() == 4 {
-                    match parts[3].parse::<u8>() {
-                        Ok(value) => value,
-                        Err(_) => {
-                            error!("Invalid background format: {value}");
-                            return None
-                        }
-                    }
-                } else {
-                    255
-                };
+        background
 
-                Some(Background(r, g, b, a))
-            }
+    }
+
+    // Flat color usable as matte when flattening transparent images
+    pub fn get_color(&self) -> Option<&Color> {
+        match self {
+            Background::Color(color) => Some(color),
+            _ => None
         }
-        
     }
-}
 
-impl From<&Background> for Vec<f64> {
-    fn from(value: &Background) -> Self {
-        vec![value.0 as f64, value.1 as f64, value.2 as f64, value.3 as f64]
+    fn color(value: &str) -> Option<Color> {
+        Color::from(&Some(value.to_string()))
     }
 }
 
@@ -142,10 +87,30 @@ mod test {
         assert_eq!(Background::from(&Some("invalid".to_string())), None);
         assert_eq!(Background::from(&Some("123".to_string())), None);
         assert_eq!(Background::from(&Some("123,123".to_string())), None);
-        assert_eq!(Background::from(&Some("123,123,123".to_string())), Some(Background(123, 123, 123, 255)));
-        assert_eq!(Background::from(&Some("123,123,123,123".to_string())), Some(Background(123, 123, 123, 123)));
-        assert_eq!(Background::from(&Some("#1234".to_string())), None);
-        assert_eq!(Background::from(&Some("#123456".to_string())), Some(Background(18, 52, 86, 255)));
-        assert_eq!(Background::from(&Some("#12345678".to_string())), Some(Background(18, 52, 86, 120)));
+        assert_eq!(Background::from(&Some("123,123,123".to_string())), Some(Background::Color(Color(123, 123, 123, 255))));
+        assert_eq!(Background::from(&Some("123,123,123,123".to_string())), Some(Background::Color(Color(123, 123, 123, 123))));
+        assert_eq!(Background::from(&Some("#1234".to_string())), Some(Background::Color(Color(17, 34, 51, 68))));
+        assert_eq!(Background::from(&Some("#123456".to_string())), Some(Background::Color(Color(18, 52, 86, 255))));
+        assert_eq!(Background::from(&Some("#12345678".to_string())), Some(Background::Color(Color(18, 52, 86, 120))));
+        assert_eq!(Background::from(&Some("tomato".to_string())), Some(Background::Color(Color(255, 99, 71, 255))));
+        assert_eq!(Background::from(&Some("hsl(0,0%,100%)".to_string())), Some(Background::Color(Color(255, 255, 255, 255))));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_background_gradient() {
+        assert_eq!(Background::from(&Some("linear:white:black".to_string())), Some(Background::Linear { from: Color(255, 255, 255, 255), to: Color(0, 0, 0, 255), angle: 180.0 }));
+        assert_eq!(Background::from(&Some("linear:#fff:rgb(0,0,0):-90".to_string())), Some(Background::Linear { from: Color(255, 255, 255, 255), to: Color(0, 0, 0, 255), angle: 270.0 }));
+        assert_eq!(Background::from(&Some("radial:white:black".to_string())), Some(Background::Radial { inner: Color(255, 255, 255, 255), outer: Color(0, 0, 0, 255) }));
+        assert_eq!(Background::from(&Some("linear:white".to_string())), None);
+        assert_eq!(Background::from(&Some("linear:white:black:up".to_string())), None);
+        assert_eq!(Background::from(&Some("radial:white:invalid".to_string())), None);
+    }
+
+    #[test]
+    fn test_background_ambient() {
+        assert_eq!(Background::from(&Some("ambient".to_string())), Some(Background::Ambient { sigma: 20.0 }));
+        assert_eq!(Background::from(&Some("ambient:5".to_string())), Some(Background::Ambient { sigma: 5.0 }));
+        assert_eq!(Background::from(&Some("ambient:500".to_string())), Some(Background::Ambient { sigma: 100.0 }));
+        assert_eq!(Background::from(&Some("ambient:0".to_string())), None);
+    }
+}
@@ -0,0 +1,56 @@
+use serde::Serialize;
+use crate::parameters::origin::Origin;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Canvas {
+    pub width: u16,
+    pub height: u16,
+    pub origin: Origin
+}
+
+impl Canvas {
+    pub fn from(value: &Option<String>) -> Option<Canvas> {
+
+        // Format: canvas=WxH[,origin]
+        let value = match value {
+            Some(value) => value,
+            None => return None
+        };
+
+        let (dimensions, origin) = match value.split_once(',') {
+            Some((dimensions, origin)) => (dimensions, Origin::from(origin)),
+            None => (value.as_str(), Origin::default())
+        };
+
+        let (width, height) = dimensions.split_once('x')?;
+
+        let width = width.parse::<u16>().ok().filter(|width| *width > 0)?;
+        let height = height.parse::<u16>().ok().filter(|height| *height > 0)?;
+
+        Some(Canvas { width, height, origin })
+
+    }
+
+    pub fn scale(self, dpr: f32) -> Canvas {
+        Canvas {
+            width: (self.width as f32 * dpr).round() as u16,
+            height: (self.height as f32 * dpr).round() as u16,
+            origin: self.origin
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canvas_from() {
+        assert_eq!(Canvas::from(&None), None);
+        assert_eq!(Canvas::from(&Some("800x600".to_string())), Some(Canvas { width: 800, height: 600, origin: Origin::Center }));
+        assert_eq!(Canvas::from(&Some("800x600,top-left".to_string())), Some(Canvas { width: 800, height: 600, origin: Origin::TopLeft }));
+        assert_eq!(Canvas::from(&Some("800".to_string())), None);
+        assert_eq!(Canvas::from(&Some("0x600".to_string())), None);
+        assert_eq!(Canvas::from(&Some("800x-1".to_string())), None);
+    }
+}
@@ -0,0 +1,382 @@
+use log::error;
+use serde::Serialize;
+
+// CSS named colors
+const NAMED_COLORS: [(&str, u32); 148] = [
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32)
+];
+
+#[derive(Default, Clone, Debug, PartialEq, Serialize)]
+pub struct Color(pub u8, pub u8, pub u8, pub u8);
+
+impl Color {
+    pub fn is_transparent(&self) -> bool {
+        self.3 == 0
+    }
+
+    pub fn from(value: &Option<String>) -> Option<Color> {
+
+        // Format: CSS name, #rgb[a], #rrggbb[aa], rrggbb[aa], rgb[a](), hsl[a]() or r,g,b[,a]
+        let value = match value {
+            Some(value) => value.trim().to_lowercase(),
+            None => return None
+        };
+
+        let color = Self::parse(&value);
+
+        if color.is_none() {
+            error!("Invalid color format: {value}");
+        }
+
+        color
+
+    }
+
+    fn parse(value: &str) -> Option<Color> {
+
+        if value == "transparent" {
+            return Some(Color(0, 0, 0, 0));
+        }
+
+        if let Some((_, rgb)) = NAMED_COLORS.iter().find(|(name, _)| *name == value) {
+            return Some(Color((rgb >> 16) as u8, (rgb >> 8) as u8, *rgb as u8, 255));
+        }
+
+        if let Some(hex) = value.strip_prefix('#') {
+            return Self::parse_hex(hex, true);
+        }
+
+        if let Some(arguments) = Self::get_arguments(value, &["rgb", "rgba"]) {
+            return Self::parse_rgb(&arguments);
+        }
+
+        if let Some(arguments) = Self::get_arguments(value, &["hsl", "hsla"]) {
+            return Self::parse_hsl(&arguments);
+        }
+
+        if value.contains(',') {
+            return Self::parse_list(value);
+        }
+
+        Self::parse_hex(value, false)
+
+    }
+
+    // Short forms (#rgb, #rgba) are accepted only with the # prefix
+    fn parse_hex(hex: &str, prefixed: bool) -> Option<Color> {
+
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let channels: Vec<u8> = match hex.len() {
+            3 | 4 if prefixed => hex.chars().map(|c| c.to_digit(16).unwrap() as u8 * 17).collect(),
+            6 | 8 => (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect(),
+            _ => return None
+        };
+
+        Some(Color(channels[0], channels[1], channels[2], *channels.get(3).unwrap_or(&255)))
+
+    }
+
+    // Format: r,g,b[,a] with all channels in range 0-255
+    fn parse_list(value: &str) -> Option<Color> {
+
+        let parts: Vec<&str> = value.split(',').collect();
+
+        if parts.len() < 3 || parts.len() > 4 {
+            return None;
+        }
+
+        let mut channels = Vec::with_capacity(4);
+
+        for part in parts {
+            channels.push(part.trim().parse::<u8>().ok()?);
+        }
+
+        Some(Color(channels[0], channels[1], channels[2], *channels.get(3).unwrap_or(&255)))
+
+    }
+
+    // Arguments may be separated by commas or spaces, alpha optionally by a slash
+    fn get_arguments<'a>(value: &'a str, functions: &[&str]) -> Option<Vec<&'a str>> {
+
+        let (function, arguments) = value.strip_suffix(')')?.split_once('(')?;
+
+        if !functions.contains(&function.trim()) {
+            return None;
+        }
+
+        let arguments: Vec<&str> = arguments.split([',', ' ', '/']).filter(|argument| !argument.is_empty()).collect();
+
+        match arguments.len() {
+            3 | 4 => Some(arguments),
+            _ => None
+        }
+
+    }
+
+    fn parse_rgb(arguments: &[&str]) -> Option<Color> {
+
+        let r = Self::parse_channel(arguments[0])?;
+        let g = Self::parse_channel(arguments[1])?;
+        let b = Self::parse_channel(arguments[2])?;
+
+        let a = match arguments.get(3) {
+            Some(alpha) => Self::parse_alpha(alpha)?,
+            None => 255
+        };
+
+        Some(Color(r, g, b, a))
+
+    }
+
+    fn parse_hsl(arguments: &[&str]) -> Option<Color> {
+
+        let hue = arguments[0].strip_suffix("deg").unwrap_or(arguments[0]).parse::<f64>().ok()?.rem_euclid(360.0) / 360.0;
+        let saturation = Self::parse_percentage(arguments[1])?;
+        let lightness = Self::parse_percentage(arguments[2])?;
+
+        let a = match arguments.get(3) {
+            Some(alpha) => Self::parse_alpha(alpha)?,
+            None => 255
+        };
+
+        let q = match lightness < 0.5 {
+            true => lightness * (1.0 + saturation),
+            false => lightness + saturation - lightness * saturation
+        };
+        let p = 2.0 * lightness - q;
+
+        let channel = |t: f64| {
+            let t = t.rem_euclid(1.0);
+
+            let value = if t < 1.0 / 6.0 {
+                p + (q - p) * 6.0 * t
+            } else if t < 0.5 {
+                q
+            } else if t < 2.0 / 3.0 {
+                p + (q - p) * (2.0 / 3.0 - t) * 6.0
+            } else {
+                p
+            };
+
+            (value * 255.0).round() as u8
+        };
+
+        Some(Color(channel(hue + 1.0 / 3.0), channel(hue), channel(hue - 1.0 / 3.0), a))
+
+    }
+
+    // Channel in range 0-255 or percentage
+    fn parse_channel(value: &str) -> Option<u8> {
+        match value.ends_with('%') {
+            true => Some((Self::parse_percentage(value)? * 255.0).round() as u8),
+            false => Some(value.parse::<f64>().ok()?.round().clamp(0.0, 255.0) as u8)
+        }
+    }
+
+    // Alpha in range 0-1 or percentage
+    fn parse_alpha(value: &str) -> Option<u8> {
+        let alpha = match value.ends_with('%') {
+            true => Self::parse_percentage(value)?,
+            false => value.parse::<f64>().ok()?.clamp(0.0, 1.0)
+        };
+
+        Some((alpha * 255.0).round() as u8)
+    }
+
+    fn parse_percentage(value: &str) -> Option<f64> {
+        Some((value.strip_suffix('%')?.parse::<f64>().ok()? / 100.0).clamp(0.0, 1.0))
+    }
+}
+
+impl From<&Color> for Vec<f64> {
+    fn from(value: &Color) -> Self {
+        vec![value.0 as f64, value.1 as f64, value.2 as f64, value.3 as f64]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_color_names() {
+        assert_eq!(Color::from(&Some("transparent".to_string())), Some(Color(0, 0, 0, 0)));
+        assert_eq!(Color::from(&Some("white".to_string())), Some(Color(255, 255, 255, 255)));
+        assert_eq!(Color::from(&Some("RebeccaPurple".to_string())), Some(Color(102, 51, 153, 255)));
+        assert_eq!(Color::from(&Some("notacolor".to_string())), None);
+    }
+
+    #[test]
+    fn test_color_hex() {
+        assert_eq!(Color::from(&Some("#fff".to_string())), Some(Color(255, 255, 255, 255)));
+        assert_eq!(Color::from(&Some("#f008".to_string())), Some(Color(255, 0, 0, 136)));
+        assert_eq!(Color::from(&Some("#7A7AD3".to_string())), Some(Color(122, 122, 211, 255)));
+        assert_eq!(Color::from(&Some("#000000ff".to_string())), Some(Color(0, 0, 0, 255)));
+        assert_eq!(Color::from(&Some("7a7ad3".to_string())), Some(Color(122, 122, 211, 255)));
+        assert_eq!(Color::from(&Some("fff".to_string())), None);
+        assert_eq!(Color::from(&Some("#ff".to_string())), None);
+        assert_eq!(Color::from(&Some("#gggggg".to_string())), None);
+    }
+
+    #[test]
+    fn test_color_functions() {
+        assert_eq!(Color::from(&Some("rgb(255, 124, 64)".to_string())), Some(Color(255, 124, 64, 255)));
+        assert_eq!(Color::from(&Some("rgba(255,124,64,0.5)".to_string())), Some(Color(255, 124, 64, 128)));
+        assert_eq!(Color::from(&Some("rgb(100% 0% 0% / 50%)".to_string())), Some(Color(255, 0, 0, 128)));
+        assert_eq!(Color::from(&Some("hsl(0, 100%, 50%)".to_string())), Some(Color(255, 0, 0, 255)));
+        assert_eq!(Color::from(&Some("hsl(120deg 100% 25%)".to_string())), Some(Color(0, 128, 0, 255)));
+        assert_eq!(Color::from(&Some("hsla(240, 100%, 50%, 0)".to_string())), Some(Color(0, 0, 255, 0)));
+        assert_eq!(Color::from(&Some("rgb(1, 2)".to_string())), None);
+        assert_eq!(Color::from(&Some("hsl(0, 100, 50)".to_string())), None);
+    }
+}
@@ -0,0 +1,117 @@
+use serde::Serialize;
+
+// Limits prevent expensive convolutions on large images
+const MAX_BLUR_SIGMA: f64 = 100.0;
+const MAX_SHARPEN_SIGMA: f64 = 10.0;
+const MAX_SHARPEN_SLOPE: f64 = 100.0;
+const MAX_PIXELATE_SIZE: u16 = 256;
+
+const DEFAULT_SHARPEN_FLAT: f64 = 0.0;
+const DEFAULT_SHARPEN_JAGGED: f64 = 3.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub struct Blur {
+    pub sigma: f64
+}
+
+impl Blur {
+    pub fn from(value: &Option<String>) -> Option<Blur> {
+
+        // Format: blur=sigma
+        let sigma = parse_positive(value.as_ref()?)?;
+
+        Some(Blur { sigma: sigma.min(MAX_BLUR_SIGMA) })
+
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub struct Sharpen {
+    pub sigma: f64,
+    pub flat: f64,
+    pub jagged: f64
+}
+
+impl Sharpen {
+    pub fn from(value: &Option<String>) -> Option<Sharpen> {
+
+        // Format: sharpen=sigma[,m1,m2], m1 is the slope for flat areas and m2 for jagged areas
+        let parts: Vec<&str> = value.as_ref()?.split(',').collect();
+
+        if parts.len() > 3 {
+            return None;
+        }
+
+        let sigma = parse_positive(parts[0])?.min(MAX_SHARPEN_SIGMA);
+
+        let flat = match parts.get(1) {
+            Some(flat) => parse_slope(flat)?,
+            None => DEFAULT_SHARPEN_FLAT
+        };
+
+        let jagged = match parts.get(2) {
+            Some(jagged) => parse_slope(jagged)?,
+            None => DEFAULT_SHARPEN_JAGGED
+        };
+
+        Some(Sharpen { sigma, flat, jagged })
+
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub struct Pixelate {
+    pub size: u16
+}
+
+impl Pixelate {
+    pub fn from(value: &Option<String>) -> Option<Pixelate> {
+
+        // Format: pixelate=size in pixels, size 1 keeps the image unchanged
+        let size = value.as_ref()?.trim().parse::<u16>().ok().filter(|size| *size > 1)?;
+
+        Some(Pixelate { size: size.min(MAX_PIXELATE_SIZE) })
+
+    }
+}
+
+fn parse_positive(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|value| value.is_finite() && *value > 0.0)
+}
+
+fn parse_slope(value: &str) -> Option<f64> {
+    value.trim().parse::<f64>().ok().filter(|value| value.is_finite() && *value >= 0.0).map(|value| value.min(MAX_SHARPEN_SLOPE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blur_from() {
+        assert_eq!(Blur::from(&None), None);
+        assert_eq!(Blur::from(&Some("invalid".to_string())), None);
+        assert_eq!(Blur::from(&Some("0".to_string())), None);
+        assert_eq!(Blur::from(&Some("2.5".to_string())), Some(Blur { sigma: 2.5 }));
+        assert_eq!(Blur::from(&Some("1000".to_string())), Some(Blur { sigma: 100.0 }));
+    }
+
+    #[test]
+    fn test_sharpen_from() {
+        assert_eq!(Sharpen::from(&None), None);
+        assert_eq!(Sharpen::from(&Some("-1".to_string())), None);
+        assert_eq!(Sharpen::from(&Some("1".to_string())), Some(Sharpen { sigma: 1.0, flat: 0.0, jagged: 3.0 }));
+        assert_eq!(Sharpen::from(&Some("1.5,1,2".to_string())), Some(Sharpen { sigma: 1.5, flat: 1.0, jagged: 2.0 }));
+        assert_eq!(Sharpen::from(&Some("50,1000".to_string())), Some(Sharpen { sigma: 10.0, flat: 100.0, jagged: 3.0 }));
+        assert_eq!(Sharpen::from(&Some("1,a".to_string())), None);
+        assert_eq!(Sharpen::from(&Some("1,2,3,4".to_string())), None);
+    }
+
+    #[test]
+    fn test_pixelate_from() {
+        assert_eq!(Pixelate::from(&None), None);
+        assert_eq!(Pixelate::from(&Some("1".to_string())), None);
+        assert_eq!(Pixelate::from(&Some("16".to_string())), Some(Pixelate { size: 16 }));
+        assert_eq!(Pixelate::from(&Some("5000".to_string())), Some(Pixelate { size: 256 }));
+    }
+}
@@ -0,0 +1,89 @@
+use serde::Serialize;
+use crate::parameters::color::Color;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum Filter {
+    Grayscale,
+    Sepia,
+    Invert,
+    Tint(Color),
+    Duotone(Color, Color)
+}
+
+impl Filter {
+    pub fn from(value: &Option<String>) -> Option<Filter> {
+
+        // Format: filter=grayscale|sepia|invert|tint:<color>|duotone:<shadows>,<highlights>
+        let value = match value {
+            Some(value) => value.trim().to_lowercase(),
+            None => return None
+        };
+
+        let (name, arguments) = match value.split_once(':') {
+            Some((name, arguments)) => (name, Some(arguments)),
+            None => (value.as_str(), None)
+        };
+
+        match (name, arguments) {
+            ("grayscale" | "greyscale", None) => Some(Filter::Grayscale),
+            ("sepia", None) => Some(Filter::Sepia),
+            ("invert", None) => Some(Filter::Invert),
+            ("tint", Some(color)) => Some(Filter::Tint(Color::from(&Some(color.to_string()))?)),
+            ("duotone", Some(colors)) => {
+                let colors = split_colors(colors);
+
+                if colors.len() != 2 {
+                    return None;
+                }
+
+                Some(Filter::Duotone(Color::from(&Some(colors[0].to_string()))?, Color::from(&Some(colors[1].to_string()))?))
+            },
+            _ => None
+        }
+
+    }
+}
+
+// Split by commas outside of color functions like rgb(r,g,b)
+fn split_colors(value: &str) -> Vec<&str> {
+
+    let mut colors = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+
+    for (index, character) in value.char_indices() {
+        match character {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                colors.push(&value[start..index]);
+                start = index + 1;
+            },
+            _ => {}
+        }
+    }
+
+    colors.push(&value[start..]);
+    colors
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_from() {
+        assert_eq!(Filter::from(&None), None);
+        assert_eq!(Filter::from(&Some("invalid".to_string())), None);
+        assert_eq!(Filter::from(&Some("grayscale".to_string())), Some(Filter::Grayscale));
+        assert_eq!(Filter::from(&Some("greyscale".to_string())), Some(Filter::Grayscale));
+        assert_eq!(Filter::from(&Some("sepia".to_string())), Some(Filter::Sepia));
+        assert_eq!(Filter::from(&Some("invert".to_string())), Some(Filter::Invert));
+        assert_eq!(Filter::from(&Some("invert:red".to_string())), None);
+        assert_eq!(Filter::from(&Some("tint:#f00".to_string())), Some(Filter::Tint(Color(255, 0, 0, 255))));
+        assert_eq!(Filter::from(&Some("tint".to_string())), None);
+        assert_eq!(Filter::from(&Some("duotone:navy,rgb(255,200,0)".to_string())), Some(Filter::Duotone(Color(0, 0, 128, 255), Color(255, 200, 0, 255))));
+        assert_eq!(Filter::from(&Some("duotone:navy".to_string())), None);
+        assert_eq!(Filter::from(&Some("duotone:navy,invalid".to_string())), None);
+    }
+}
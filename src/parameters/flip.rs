@@ -0,0 +1,49 @@
+use serde::Serialize;
+
+#[derive(Default, Copy, Clone, Debug, Serialize, PartialEq)]
+pub enum Flip {
+    #[default]
+    No,
+    Horizontal,
+    Vertical,
+    Both
+}
+
+impl Flip {
+
+    pub fn from(value: &Option<String>) -> Self {
+
+        let value = match value {
+            Some(value) => value,
+            None => return Self::default()
+        };
+
+        match value.as_str() {
+            "h" | "horizontal" => Flip::Horizontal,
+            "v" | "vertical" => Flip::Vertical,
+            "both" | "hv" | "vh" => Flip::Both,
+            _ => Flip::No
+        }
+
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flip_from() {
+        assert_eq!(Flip::from(&None), Flip::No);
+        assert_eq!(Flip::from(&Some("".to_string())), Flip::No);
+        assert_eq!(Flip::from(&Some("invalid".to_string())), Flip::No);
+        assert_eq!(Flip::from(&Some("h".to_string())), Flip::Horizontal);
+        assert_eq!(Flip::from(&Some("horizontal".to_string())), Flip::Horizontal);
+        assert_eq!(Flip::from(&Some("v".to_string())), Flip::Vertical);
+        assert_eq!(Flip::from(&Some("vertical".to_string())), Flip::Vertical);
+        assert_eq!(Flip::from(&Some("both".to_string())), Flip::Both);
+        assert_eq!(Flip::from(&Some("hv".to_string())), Flip::Both);
+        assert_eq!(Flip::from(&Some("vh".to_string())), Flip::Both);
+    }
+}
@@ -8,6 +8,10 @@ pub enum Format {
     Png,
     Webp,
     Avif,
+    Jxl,
+    Gif,
+    Tiff,
+    Heic,
     Pdf
 }
 
@@ -25,6 +29,10 @@ impl Format {
             "png" => Format::Png,
             "webp" => Format::Webp,
             "avif" => Format::Avif,
+            "jxl" => Format::Jxl,
+            "gif" => Format::Gif,
+            "tif" | "tiff" => Format::Tiff,
+            "heic" => Format::Heic,
             "pdf" => Format::Pdf,
             _ => Format::Auto
         }
@@ -38,8 +46,27 @@ impl Format {
             Format::Png => "png",
             Format::Webp => "webp",
             Format::Avif => "avif",
+            Format::Jxl => "jxl",
+            Format::Gif => "gif",
+            Format::Tiff => "tiff",
+            Format::Heic => "heic",
             Format::Pdf => "pdf"
         }
     }
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_from() {
+        assert_eq!(Format::from(&None), Format::Auto);
+        assert_eq!(Format::from(&Some("jxl".to_string())), Format::Jxl);
+        assert_eq!(Format::from(&Some("jpeg".to_string())), Format::Jpg);
+        assert_eq!(Format::from(&Some("tif".to_string())), Format::Tiff);
+        assert_eq!(Format::from(&Some("bmp".to_string())), Format::Auto);
+        assert_eq!(Format::Jxl.as_str(), "jxl");
+    }
+}
@@ -0,0 +1,48 @@
+use serde::Serialize;
+
+#[derive(Default, Clone, Debug, PartialEq, Serialize)]
+pub enum ColorProfile {
+    #[default]
+    Srgb,
+    P3,
+    Keep,
+    Named(String)
+}
+
+impl ColorProfile {
+
+    pub fn from(value: &Option<String>) -> Self {
+
+        // Format: icc=srgb|p3|keep|{profile name}
+        let value = match value {
+            Some(value) => value,
+            None => return Self::default()
+        };
+
+        match value.as_str() {
+            "srgb" => ColorProfile::Srgb,
+            "p3" => ColorProfile::P3,
+            "keep" => ColorProfile::Keep,
+            name if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') => ColorProfile::Named(name.to_string()),
+            _ => ColorProfile::Srgb
+        }
+
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_profile_from() {
+        assert_eq!(ColorProfile::from(&None), ColorProfile::Srgb);
+        assert_eq!(ColorProfile::from(&Some("".to_string())), ColorProfile::Srgb);
+        assert_eq!(ColorProfile::from(&Some("srgb".to_string())), ColorProfile::Srgb);
+        assert_eq!(ColorProfile::from(&Some("p3".to_string())), ColorProfile::P3);
+        assert_eq!(ColorProfile::from(&Some("keep".to_string())), ColorProfile::Keep);
+        assert_eq!(ColorProfile::from(&Some("adobe-rgb_1998".to_string())), ColorProfile::Named("adobe-rgb_1998".to_string()));
+        assert_eq!(ColorProfile::from(&Some("../secret".to_string())), ColorProfile::Srgb);
+    }
+}
@@ -0,0 +1,54 @@
+use serde::Serialize;
+
+const MAX_PERCENT: f32 = 1000.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub enum Length {
+    Pixels(u16),
+    Percent(f32)
+}
+
+impl Length {
+    pub fn from(value: &str) -> Option<Length> {
+
+        // Format: 10 (pixels) or 10% (percent of the related dimension)
+        let value = value.trim();
+
+        match value.strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().ok().filter(|percent| *percent >= 0.0 && *percent <= MAX_PERCENT).map(Length::Percent),
+            None => value.parse::<u16>().ok().map(Length::Pixels)
+        }
+
+    }
+
+    // Pixel lengths are multiplied by device pixel ratio like dimensions
+    pub fn scale(self, dpr: f32) -> Length {
+        match self {
+            Length::Pixels(pixels) => Length::Pixels((pixels as f32 * dpr).round() as u16),
+            Length::Percent(_) => self
+        }
+    }
+
+    pub fn resolve(&self, dimension: i32) -> i32 {
+        match self {
+            Length::Pixels(pixels) => *pixels as i32,
+            Length::Percent(percent) => (dimension as f64 * *percent as f64 / 100.0).round() as i32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_length_from() {
+        assert_eq!(Length::from("10"), Some(Length::Pixels(10)));
+        assert_eq!(Length::from(" 12.5% "), Some(Length::Percent(12.5)));
+        assert_eq!(Length::from("-10"), None);
+        assert_eq!(Length::from("-10%"), None);
+        assert_eq!(Length::from("abc"), None);
+        assert_eq!(Length::from("10%").unwrap().resolve(300), 30);
+        assert_eq!(Length::from("10").unwrap().scale(2.0).resolve(300), 20);
+    }
+}
@@ -0,0 +1,60 @@
+use std::path::{Component, Path};
+
+use serde::Serialize;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub enum Mask {
+    Circle,
+    Ellipse,
+    Svg(String)
+}
+
+impl Mask {
+    pub fn from(value: &Option<String>) -> Option<Mask> {
+
+        // Format: mask=circle or mask=ellipse or mask=<path to SVG file relative to the working directory>
+        let value = match value {
+            Some(value) => value.trim(),
+            None => return None
+        };
+
+        match value {
+            "circle" => Some(Mask::Circle),
+            "ellipse" => Some(Mask::Ellipse),
+            path if is_valid_path(path) => Some(Mask::Svg(path.to_string())),
+            _ => None
+        }
+
+    }
+}
+
+// Mask files must stay within the working directory like served files
+fn is_valid_path(path: &str) -> bool {
+
+    let path = Path::new(path);
+
+    !path.as_os_str().is_empty()
+        && path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("svg"))
+        && path.components().all(|component| matches!(component, Component::Normal(_)))
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask_from() {
+        assert_eq!(Mask::from(&None), None);
+        assert_eq!(Mask::from(&Some("circle".to_string())), Some(Mask::Circle));
+        assert_eq!(Mask::from(&Some("ellipse".to_string())), Some(Mask::Ellipse));
+        assert_eq!(Mask::from(&Some("data/masks/star.svg".to_string())), Some(Mask::Svg("data/masks/star.svg".to_string())));
+        assert_eq!(Mask::from(&Some("data/masks/star.SVG".to_string())), Some(Mask::Svg("data/masks/star.SVG".to_string())));
+        assert_eq!(Mask::from(&Some("data/masks/star.png".to_string())), None);
+        assert_eq!(Mask::from(&Some("../masks/star.svg".to_string())), None);
+        assert_eq!(Mask::from(&Some("data/../../star.svg".to_string())), None);
+        assert_eq!(Mask::from(&Some("/etc/star.svg".to_string())), None);
+        assert_eq!(Mask::from(&Some("./star.svg".to_string())), None);
+        assert_eq!(Mask::from(&Some("square".to_string())), None);
+    }
+}
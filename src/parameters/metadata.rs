@@ -0,0 +1,62 @@
+use serde::Serialize;
+
+#[derive(Default, Clone, Debug, PartialEq, Serialize)]
+pub struct Metadata {
+    pub icc: bool,
+    pub copyright: bool,
+    pub exif: bool,
+    pub other: bool
+}
+
+impl Metadata {
+
+    pub fn from(value: &Option<String>) -> Self {
+
+        let mut metadata = Self::default();
+
+        // Format: meta=none|all or meta={icc|copyright|exif-without-gps},...
+        let value = match value {
+            Some(value) => value,
+            None => return metadata
+        };
+
+        for part in value.split(',') {
+            match part {
+                "icc" => metadata.icc = true,
+                "copyright" => metadata.copyright = true,
+                "exif-without-gps" | "exif" => {
+                    metadata.copyright = true;
+                    metadata.exif = true;
+                },
+                "all" => {
+                    metadata.icc = true;
+                    metadata.copyright = true;
+                    metadata.exif = true;
+                    metadata.other = true;
+                },
+                _ => {}
+            }
+        }
+
+        metadata
+
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_from() {
+        assert_eq!(Metadata::from(&None), Metadata::default());
+        assert_eq!(Metadata::from(&Some("none".to_string())), Metadata::default());
+        assert_eq!(Metadata::from(&Some("invalid".to_string())), Metadata::default());
+        assert_eq!(Metadata::from(&Some("icc".to_string())), Metadata { icc: true, ..Metadata::default() });
+        assert_eq!(Metadata::from(&Some("icc,copyright".to_string())), Metadata { icc: true, copyright: true, ..Metadata::default() });
+        assert_eq!(Metadata::from(&Some("exif-without-gps".to_string())), Metadata { copyright: true, exif: true, ..Metadata::default() });
+        assert_eq!(Metadata::from(&Some("all".to_string())), Metadata { icc: true, copyright: true, exif: true, other: true });
+        assert_eq!(Metadata::from(&Some("none".to_string())), Metadata::default());
+    }
+}
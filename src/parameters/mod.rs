@@ -3,20 +3,57 @@ use std::path::Path;
 
 use serde::{Deserialize, Serialize};
 
+pub use adjust::{Adjustments, Auto};
 pub use background::Background;
+pub use canvas::Canvas;
+pub use color::Color;
 pub use crop::Crop;
+pub use effects::{Blur, Pixelate, Sharpen};
+pub use filter::Filter;
+pub use flip::Flip;
+pub use icc::ColorProfile;
+pub use mask::Mask;
+pub use metadata::Metadata;
+pub use origin::Origin;
+pub use pad::Padding;
+pub use quality::{Quality, Target};
+pub use radius::Radius;
+pub use redact::{Redact, RedactMode};
 pub use rotate::Rotate;
+pub use subsample::Subsample;
+pub use text::Text;
 pub use thumbnail::Thumbnail;
+pub use trim::Trim;
+pub use watermark::Watermark;
 
 use crate::crypto::verify_hmac;
+use crate::services::watermarks;
 use crate::parameters::format::Format;
 
 pub mod background;
+pub mod color;
 pub mod rotate;
 pub mod thumbnail;
 pub mod origin;
 pub mod crop;
 pub mod format;
+pub mod subsample;
+pub mod quality;
+pub mod metadata;
+pub mod icc;
+pub mod length;
+pub mod pad;
+pub mod canvas;
+pub mod trim;
+pub mod flip;
+pub mod effects;
+pub mod adjust;
+pub mod filter;
+pub mod redact;
+pub mod watermark;
+pub mod text;
+pub mod radius;
+pub mod mask;
 
 pub type ParametersResult<T> = Result<T, &'static str>;
 
@@ -24,22 +61,66 @@ pub type ParametersResult<T> = Result<T, &'static str>;
 pub struct RawUrlParameters {
     w: Option<u16>,
     h: Option<u16>,
-    q: Option<u8>,
+    q: Option<String>,
     dpr: Option<f32>,
     crop: Option<String>,
     thumb: Option<String>,
     original: Option<bool>,
     rot: Option<String>,
+    flip: Option<String>,
+    autorot: Option<bool>,
+    orient: Option<u8>,
     bg: Option<String>,
     f: Option<String>,
+    frame: Option<u32>,
+    lossless: Option<bool>,
+    nearlossless: Option<bool>,
+    progressive: Option<bool>,
+    palette: Option<bool>,
+    subsample: Option<String>,
+    profile: Option<String>,
+    meta: Option<String>,
+    icc: Option<String>,
+    depth: Option<u8>,
+    pad: Option<String>,
+    canvas: Option<String>,
+    trim: Option<String>,
+    blur: Option<String>,
+    sharpen: Option<String>,
+    pixelate: Option<String>,
+    brightness: Option<String>,
+    contrast: Option<String>,
+    saturation: Option<String>,
+    hue: Option<String>,
+    gamma: Option<String>,
+    auto: Option<String>,
+    filter: Option<String>,
+    redact: Option<String>,
+    wm: Option<String>,
+    text: Option<String>,
+    text_font: Option<String>,
+    text_size: Option<String>,
+    text_color: Option<String>,
+    text_origin: Option<String>,
+    text_pad: Option<String>,
+    text_bg: Option<String>,
+    radius: Option<String>,
+    mask: Option<String>,
     token: Option<String>
 }
 
 impl RawUrlParameters {
     pub fn verify_token(&self, path: &str, url_parameters: &HashMap<String, String>) -> ParametersResult<()> {
 
+        // Invalid redaction is rejected instead of serving the image unredacted
+        if self.redact.is_some() && Redact::from(&self.redact).is_none() {
+            return Err("Invalid redaction");
+        }
+
+        // Redaction must not be removable from the URL, so it is only accepted for signed URLs
         let env_key = match std::env::var("KEY") {
             Ok(key) => key,
+            Err(_) if self.redact.is_some() => return Err("Redaction requires signed URLs"),
             Err(_) => return Ok(())
         };
 
@@ -71,8 +152,35 @@ pub struct UrlParameters<'a> {
     pub thumbnail: Thumbnail,
     pub original: bool,
     pub rotate: Rotate,
+    pub flip: Flip,
+    pub autorotate: bool,
+    pub orientation: Option<u8>,
     pub background: Option<Background>,
-    pub format: Format
+    pub format: Format,
+    pub frame: Option<u32>,
+    pub lossless: bool,
+    pub near_lossless: bool,
+    pub progressive: bool,
+    pub palette: bool,
+    pub subsample: Option<Subsample>,
+    pub profile: Option<String>,
+    pub metadata: Metadata,
+    pub color_profile: ColorProfile,
+    pub depth: u8,
+    pub padding: Option<Padding>,
+    pub canvas: Option<Canvas>,
+    pub trim: Option<Trim>,
+    pub blur: Option<Blur>,
+    pub sharpen: Option<Sharpen>,
+    pub pixelate: Option<Pixelate>,
+    pub adjustments: Adjustments,
+    pub filter: Option<Filter>,
+    pub redact: Option<Redact>,
+    pub watermark: Option<Watermark>,
+    pub mandatory_watermark: Option<Watermark>,
+    pub text: Option<Text>,
+    pub radius: Option<Radius>,
+    pub mask: Option<Mask>
 }
 
 impl<'a> UrlParameters<'a> {
@@ -86,23 +194,42 @@ impl<'a> UrlParameters<'a> {
             path: Path::new(path),
             width,
             height,
-            quality: match value.q {
-                Some(q) => Quality::Custom(q),
-                None => Quality::Default
-            },
+            quality: Quality::from(&value.q),
             crop: Crop::from(&value.crop),
             thumbnail: Thumbnail::from(&value.thumb),
             original: value.original.unwrap_or(false),
             rotate: Rotate::from(&value.rot),
+            flip: Flip::from(&value.flip),
+            autorotate: value.autorot.unwrap_or(true),
+            orientation: value.orient.filter(|orientation| (1..=8).contains(orientation)),
             background: Background::from(&value.bg),
-            format: Format::from(&value.f)
+            format: Format::from(&value.f),
+            frame: value.frame.map(|frame| frame.max(1)),
+            lossless: value.lossless.unwrap_or(false),
+            near_lossless: value.nearlossless.unwrap_or(false),
+            progressive: value.progressive.unwrap_or(false),
+            palette: value.palette.unwrap_or(true),
+            subsample: Subsample::from(&value.subsample),
+            // Unsigned clients must not select expensive encoding profiles
+            profile: value.profile.filter(|_| std::env::var("KEY").is_ok()),
+            metadata: Metadata::from(&value.meta),
+            color_profile: ColorProfile::from(&value.icc),
+            depth: value.depth.filter(|depth| matches!(depth, 8 | 10 | 12 | 16)).unwrap_or(8),
+            padding: Padding::from(&value.pad).map(|padding| padding.scale(dpr)),
+            canvas: Canvas::from(&value.canvas).map(|canvas| canvas.scale(dpr)),
+            trim: Trim::from(&value.trim),
+            blur: Blur::from(&value.blur),
+            sharpen: Sharpen::from(&value.sharpen),
+            pixelate: Pixelate::from(&value.pixelate),
+            adjustments: Adjustments::from(&value.brightness, &value.contrast, &value.saturation, &value.hue, &value.gamma, &value.auto),
+            filter: Filter::from(&value.filter),
+            redact: Redact::from(&value.redact),
+            watermark: Watermark::from(&value.wm),
+            mandatory_watermark: watermarks::get_mandatory(Path::new(path)),
+            text: Text::from(&value.text, &value.text_font, &value.text_size, &value.text_color, &value.text_origin, &value.text_pad, &value.text_bg).map(|text| text.scale(dpr)),
+            radius: Radius::from(&value.radius).map(|radius| radius.scale(dpr)),
+            mask: Mask::from(&value.mask)
         }
         
     }
-}
-
-#[derive(Debug, Serialize)]
-pub enum Quality {
-    Default,
-    Custom(u8)
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 use serde::Serialize;
 
-#[derive(Default, Debug, PartialEq, Serialize)]
+#[derive(Default, Clone, Copy, Debug, PartialEq, Serialize)]
 pub enum Origin {
     #[default]
     Center,
@@ -28,4 +28,26 @@ impl From<&str> for Origin {
             _ => Origin::Center
         }
     }
-}
\ No newline at end of file
+}
+
+impl Origin {
+    // Position of the inner area placed within the outer area
+    pub fn position(&self, (outer_width, outer_height): (i32, i32), (inner_width, inner_height): (i32, i32)) -> (i32, i32) {
+
+        let (left, center, right) = (0, (outer_width - inner_width) / 2, outer_width - inner_width);
+        let (top, middle, bottom) = (0, (outer_height - inner_height) / 2, outer_height - inner_height);
+
+        match self {
+            Origin::Center => (center, middle),
+            Origin::TopLeft => (left, top),
+            Origin::TopCenter => (center, top),
+            Origin::TopRight => (right, top),
+            Origin::LeftCenter => (left, middle),
+            Origin::RightCenter => (right, middle),
+            Origin::BottomLeft => (left, bottom),
+            Origin::BottomCenter => (center, bottom),
+            Origin::BottomRight => (right, bottom)
+        }
+
+    }
+}
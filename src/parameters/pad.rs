@@ -0,0 +1,69 @@
+use serde::Serialize;
+use crate::parameters::length::Length;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Padding {
+    pub top: Length,
+    pub right: Length,
+    pub bottom: Length,
+    pub left: Length
+}
+
+impl Padding {
+    pub fn from(value: &Option<String>) -> Option<Padding> {
+
+        // Format: pad=all or pad=vertical,horizontal or pad=top,horizontal,bottom or pad=top,right,bottom,left
+        let value = match value {
+            Some(value) => value,
+            None => return None
+        };
+
+        let mut lengths = Vec::with_capacity(4);
+
+        for part in value.split(',') {
+            lengths.push(Length::from(part)?);
+        }
+
+        let (top, right, bottom, left) = match lengths[..] {
+            [all] => (all, all, all, all),
+            [vertical, horizontal] => (vertical, horizontal, vertical, horizontal),
+            [top, horizontal, bottom] => (top, horizontal, bottom, horizontal),
+            [top, right, bottom, left] => (top, right, bottom, left),
+            _ => return None
+        };
+
+        Some(Padding { top, right, bottom, left })
+
+    }
+
+    pub fn scale(self, dpr: f32) -> Padding {
+        Padding {
+            top: self.top.scale(dpr),
+            right: self.right.scale(dpr),
+            bottom: self.bottom.scale(dpr),
+            left: self.left.scale(dpr)
+        }
+    }
+
+    // Padding in pixels as (top, right, bottom, left), percent of image height for vertical and width for horizontal sides
+    pub fn resolve(&self, width: i32, height: i32) -> (i32, i32, i32, i32) {
+        (self.top.resolve(height), self.right.resolve(width), self.bottom.resolve(height), self.left.resolve(width))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_padding_from() {
+        assert_eq!(Padding::from(&None), None);
+        assert_eq!(Padding::from(&Some("".to_string())), None);
+        assert_eq!(Padding::from(&Some("10".to_string())).unwrap().resolve(100, 50), (10, 10, 10, 10));
+        assert_eq!(Padding::from(&Some("10,5%".to_string())).unwrap().resolve(100, 50), (10, 5, 10, 5));
+        assert_eq!(Padding::from(&Some("1,2,3".to_string())).unwrap().resolve(100, 50), (1, 2, 3, 2));
+        assert_eq!(Padding::from(&Some("10%,1,2,3".to_string())).unwrap().resolve(100, 50), (5, 1, 2, 3));
+        assert_eq!(Padding::from(&Some("1,2,3,4,5".to_string())), None);
+        assert_eq!(Padding::from(&Some("1,a".to_string())), None);
+    }
+}
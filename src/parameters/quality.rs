@@ -0,0 +1,71 @@
+use serde::Serialize;
+
+#[derive(Default, Copy, Clone, Debug, PartialEq, Serialize)]
+pub enum Quality {
+    #[default]
+    Default,
+    Custom(u8),
+    Target(Target)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub enum Target {
+    Low,
+    Good,
+    Best
+}
+
+impl Target {
+    // Minimum structural similarity of the encoded image to the original
+    pub fn ssim(&self) -> f64 {
+        match self {
+            Target::Low => 0.94,
+            Target::Good => 0.97,
+            Target::Best => 0.99
+        }
+    }
+}
+
+impl Quality {
+
+    pub fn from(value: &Option<String>) -> Self {
+
+        // Format: q={1-100} or q=auto or q=auto:{low|good|best}
+        let value = match value {
+            Some(value) => value,
+            None => return Self::default()
+        };
+
+        if let Ok(quality) = value.parse::<u8>() {
+            return Quality::Custom(quality.clamp(1, 100));
+        }
+
+        match value.as_str() {
+            "auto:low" => Quality::Target(Target::Low),
+            "auto:good" => Quality::Target(Target::Good),
+            "auto:best" => Quality::Target(Target::Best),
+            _ => Quality::Default
+        }
+
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quality_from() {
+        assert_eq!(Quality::from(&None), Quality::Default);
+        assert_eq!(Quality::from(&Some("".to_string())), Quality::Default);
+        assert_eq!(Quality::from(&Some("auto".to_string())), Quality::Default);
+        assert_eq!(Quality::from(&Some("auto:invalid".to_string())), Quality::Default);
+        assert_eq!(Quality::from(&Some("0".to_string())), Quality::Custom(1));
+        assert_eq!(Quality::from(&Some("50".to_string())), Quality::Custom(50));
+        assert_eq!(Quality::from(&Some("255".to_string())), Quality::Custom(100));
+        assert_eq!(Quality::from(&Some("auto:low".to_string())), Quality::Target(Target::Low));
+        assert_eq!(Quality::from(&Some("auto:good".to_string())), Quality::Target(Target::Good));
+        assert_eq!(Quality::from(&Some("auto:best".to_string())), Quality::Target(Target::Best));
+    }
+}
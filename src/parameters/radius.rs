@@ -0,0 +1,83 @@
+use serde::Serialize;
+use crate::parameters::length::Length;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Radius {
+    pub top_left: Length,
+    pub top_right: Length,
+    pub bottom_right: Length,
+    pub bottom_left: Length
+}
+
+impl Radius {
+    pub fn from(value: &Option<String>) -> Option<Radius> {
+
+        // Format: radius=all or radius=top-left-and-bottom-right,top-right-and-bottom-left or radius=top-left,top-right-and-bottom-left,bottom-right or radius=top-left,top-right,bottom-right,bottom-left
+        let value = match value {
+            Some(value) => value,
+            None => return None
+        };
+
+        let mut lengths = Vec::with_capacity(4);
+
+        for part in value.split(',') {
+            lengths.push(Length::from(part)?);
+        }
+
+        let (top_left, top_right, bottom_right, bottom_left) = match lengths[..] {
+            [all] => (all, all, all, all),
+            [main, cross] => (main, cross, main, cross),
+            [top_left, cross, bottom_right] => (top_left, cross, bottom_right, cross),
+            [top_left, top_right, bottom_right, bottom_left] => (top_left, top_right, bottom_right, bottom_left),
+            _ => return None
+        };
+
+        let radius = Radius { top_left, top_right, bottom_right, bottom_left };
+
+        match radius.resolve(i32::MAX, i32::MAX) == (0, 0, 0, 0) {
+            true => None,
+            false => Some(radius)
+        }
+
+    }
+
+    pub fn scale(self, dpr: f32) -> Radius {
+        Radius {
+            top_left: self.top_left.scale(dpr),
+            top_right: self.top_right.scale(dpr),
+            bottom_right: self.bottom_right.scale(dpr),
+            bottom_left: self.bottom_left.scale(dpr)
+        }
+    }
+
+    // Radii in pixels as (top-left, top-right, bottom-right, bottom-left), percent of the shorter side, max. half of the shorter side
+    pub fn resolve(&self, width: i32, height: i32) -> (i32, i32, i32, i32) {
+
+        let side = width.min(height);
+        let resolve = |length: &Length| length.resolve(side).clamp(0, side / 2);
+
+        (resolve(&self.top_left), resolve(&self.top_right), resolve(&self.bottom_right), resolve(&self.bottom_left))
+
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_radius_from() {
+        assert_eq!(Radius::from(&None), None);
+        assert_eq!(Radius::from(&Some("".to_string())), None);
+        assert_eq!(Radius::from(&Some("0".to_string())), None);
+        assert_eq!(Radius::from(&Some("0,0%".to_string())), None);
+        assert_eq!(Radius::from(&Some("10".to_string())).unwrap().resolve(100, 50), (10, 10, 10, 10));
+        assert_eq!(Radius::from(&Some("10,20%".to_string())).unwrap().resolve(100, 50), (10, 10, 10, 10));
+        assert_eq!(Radius::from(&Some("1,2,3".to_string())).unwrap().resolve(100, 50), (1, 2, 3, 2));
+        assert_eq!(Radius::from(&Some("1,2,3,4".to_string())).unwrap().resolve(100, 50), (1, 2, 3, 4));
+        assert_eq!(Radius::from(&Some("50%".to_string())).unwrap().resolve(100, 60), (30, 30, 30, 30));
+        assert_eq!(Radius::from(&Some("1000".to_string())).unwrap().resolve(100, 60), (30, 30, 30, 30));
+        assert_eq!(Radius::from(&Some("1,2,3,4,5".to_string())), None);
+        assert_eq!(Radius::from(&Some("1,a".to_string())), None);
+    }
+}
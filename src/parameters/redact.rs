@@ -0,0 +1,98 @@
+use serde::Serialize;
+use crate::parameters::length::Length;
+
+const MAX_REGIONS: usize = 32;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub enum RedactMode {
+    Blur,
+    Pixelate
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Region {
+    pub x: Length,
+    pub y: Length,
+    pub width: Length,
+    pub height: Length
+}
+
+impl Region {
+    // Area in pixels as (x, y, width, height) clamped to the image, horizontal values relative to width and vertical to height
+    pub fn resolve(&self, image_width: i32, image_height: i32) -> (i32, i32, i32, i32) {
+
+        let x = self.x.resolve(image_width).min(image_width);
+        let y = self.y.resolve(image_height).min(image_height);
+        let width = self.width.resolve(image_width).min(image_width - x);
+        let height = self.height.resolve(image_height).min(image_height - y);
+
+        (x, y, width, height)
+
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Redact {
+    pub mode: RedactMode,
+    pub regions: Vec<Region>
+}
+
+impl Redact {
+    pub fn from(value: &Option<String>) -> Option<Redact> {
+
+        // Format: redact=[blur:|pixelate:]x,y,w,h[;x,y,w,h...], values in pixels of the original image or percent
+        let value = match value {
+            Some(value) => value.trim(),
+            None => return None
+        };
+
+        let (mode, value) = match value.split_once(':') {
+            Some(("blur", regions)) => (RedactMode::Blur, regions),
+            Some(("pixelate", regions)) => (RedactMode::Pixelate, regions),
+            Some(_) => return None,
+            None => (RedactMode::Blur, value)
+        };
+
+        let mut regions = Vec::new();
+
+        for region in value.split(';') {
+            let lengths: Vec<Length> = region.split(',').map(Length::from).collect::<Option<Vec<Length>>>()?;
+
+            if lengths.len() != 4 {
+                return None;
+            }
+
+            regions.push(Region { x: lengths[0], y: lengths[1], width: lengths[2], height: lengths[3] });
+        }
+
+        if regions.len() > MAX_REGIONS {
+            return None;
+        }
+
+        Some(Redact { mode, regions })
+
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_from() {
+        assert_eq!(Redact::from(&None), None);
+        assert_eq!(Redact::from(&Some("".to_string())), None);
+        assert_eq!(Redact::from(&Some("10,10,100".to_string())), None);
+        assert_eq!(Redact::from(&Some("smudge:10,10,100,100".to_string())), None);
+
+        let redact = Redact::from(&Some("10,20,100,50".to_string())).unwrap();
+        assert_eq!(redact.mode, RedactMode::Blur);
+        assert_eq!(redact.regions[0].resolve(1000, 1000), (10, 20, 100, 50));
+
+        let redact = Redact::from(&Some("pixelate:10,10,100,100;50%,50%,10%,100%".to_string())).unwrap();
+        assert_eq!(redact.mode, RedactMode::Pixelate);
+        assert_eq!(redact.regions.len(), 2);
+        assert_eq!(redact.regions[1].resolve(400, 200), (200, 100, 40, 100));
+        assert_eq!(redact.regions[0].resolve(50, 50), (10, 10, 40, 40));
+    }
+}
@@ -1,12 +1,14 @@
 use serde::Serialize;
 
+// Rotation in degrees anticlockwise
 #[derive(Default, Copy, Clone, Debug, Serialize, PartialEq)]
 pub enum Rotate {
     #[default]
-    No = 0,
-    Left = 90,
-    UpsideDown = 180,
-    Right = 270,
+    No,
+    Left,
+    UpsideDown,
+    Right,
+    Angle(f64)
 }
 
 impl Rotate {
@@ -19,12 +21,55 @@ impl Rotate {
         };
 
         match value.as_str() {
-            "90" | "left" | "anticlockwise" => Rotate::Right,
-            "180" | "bottom-up" | "upside-down" => Rotate::UpsideDown,
-            "270" | "right" | "clockwise" => Rotate::Left,
-            _ => Rotate::No
+            "left" | "anticlockwise" => Rotate::Left,
+            "bottom-up" | "upside-down" => Rotate::UpsideDown,
+            "right" | "clockwise" => Rotate::Right,
+            _ => match value.strip_suffix("deg").unwrap_or(value).parse::<f64>() {
+                Ok(angle) if angle.is_finite() => Self::from_degrees(angle),
+                _ => Rotate::No
+            }
         }
 
     }
 
-}
\ No newline at end of file
+    fn from_degrees(angle: f64) -> Self {
+        match angle.rem_euclid(360.0) {
+            0.0 => Rotate::No,
+            90.0 => Rotate::Left,
+            180.0 => Rotate::UpsideDown,
+            270.0 => Rotate::Right,
+            angle => Rotate::Angle(angle)
+        }
+    }
+
+    pub fn degrees(&self) -> f64 {
+        match self {
+            Rotate::No => 0.0,
+            Rotate::Left => 90.0,
+            Rotate::UpsideDown => 180.0,
+            Rotate::Right => 270.0,
+            Rotate::Angle(angle) => *angle
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotate_from() {
+        assert_eq!(Rotate::from(&None), Rotate::No);
+        assert_eq!(Rotate::from(&Some("left".to_string())), Rotate::Left);
+        assert_eq!(Rotate::from(&Some("90".to_string())), Rotate::Left);
+        assert_eq!(Rotate::from(&Some("-270".to_string())), Rotate::Left);
+        assert_eq!(Rotate::from(&Some("clockwise".to_string())), Rotate::Right);
+        assert_eq!(Rotate::from(&Some("270".to_string())), Rotate::Right);
+        assert_eq!(Rotate::from(&Some("180deg".to_string())), Rotate::UpsideDown);
+        assert_eq!(Rotate::from(&Some("360".to_string())), Rotate::No);
+        assert_eq!(Rotate::from(&Some("45".to_string())), Rotate::Angle(45.0));
+        assert_eq!(Rotate::from(&Some("-15.5".to_string())), Rotate::Angle(344.5));
+        assert_eq!(Rotate::from(&Some("invalid".to_string())), Rotate::No);
+    }
+}
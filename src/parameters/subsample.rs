@@ -0,0 +1,46 @@
+use serde::Serialize;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+pub enum Subsample {
+    Auto,
+    On,
+    Off
+}
+
+impl Subsample {
+
+    pub fn from(value: &Option<String>) -> Option<Self> {
+
+        // Format: subsample=auto|on|off|420|444
+        let value = match value {
+            Some(value) => value,
+            None => return None
+        };
+
+        match value.as_str() {
+            "auto" => Some(Subsample::Auto),
+            "on" | "420" => Some(Subsample::On),
+            "off" | "444" => Some(Subsample::Off),
+            _ => None
+        }
+
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subsample_from() {
+        assert_eq!(Subsample::from(&None), None);
+        assert_eq!(Subsample::from(&Some("".to_string())), None);
+        assert_eq!(Subsample::from(&Some("invalid".to_string())), None);
+        assert_eq!(Subsample::from(&Some("auto".to_string())), Some(Subsample::Auto));
+        assert_eq!(Subsample::from(&Some("on".to_string())), Some(Subsample::On));
+        assert_eq!(Subsample::from(&Some("420".to_string())), Some(Subsample::On));
+        assert_eq!(Subsample::from(&Some("off".to_string())), Some(Subsample::Off));
+        assert_eq!(Subsample::from(&Some("444".to_string())), Some(Subsample::Off));
+    }
+}
@@ -0,0 +1,138 @@
+use serde::Serialize;
+use crate::parameters::color::Color;
+use crate::parameters::origin::Origin;
+
+const MAX_LENGTH: usize = 256;
+const MAX_FONT_LENGTH: usize = 64;
+const DEFAULT_SIZE: u16 = 24;
+const MAX_SIZE: u16 = 512;
+const MAX_PADDING: u16 = 512;
+
+// Pango reads trailing words of a font description as style options
+const FONT_STYLES: [&str; 46] = [
+    "normal", "roman", "oblique", "italic", "small-caps", "all-small-caps", "petite-caps", "all-petite-caps", "unicase", "title-caps",
+    "ultra-condensed", "extra-condensed", "condensed", "semi-condensed", "semi-expanded", "expanded", "extra-expanded", "ultra-expanded",
+    "thin", "ultra-light", "extra-light", "light", "semi-light", "demi-light", "book", "regular", "medium", "semi-bold", "demi-bold",
+    "bold", "ultra-bold", "extra-bold", "heavy", "black", "ultra-black", "extra-black", "ultra-heavy", "extra-heavy",
+    "not-rotated", "south", "upside-down", "north", "rotated-left", "east", "rotated-right", "west"
+];
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Text {
+    pub content: String,
+    pub font: Option<String>,
+    pub size: u16,
+    pub color: Color,
+    pub origin: Origin,
+    pub padding: u16,
+    pub background: Option<Color>
+}
+
+impl Text {
+    pub fn from(content: &Option<String>, font: &Option<String>, size: &Option<String>, color: &Option<String>, origin: &Option<String>, padding: &Option<String>, background: &Option<String>) -> Option<Text> {
+
+        // Format: text=content, styled by text_font, text_size, text_color, text_origin, text_pad and text_bg
+        let content = content.as_ref()?.trim();
+
+        if content.is_empty() {
+            return None;
+        }
+
+        Some(Text {
+            content: content.chars().take(MAX_LENGTH).collect(),
+            font: parse_font(font),
+            size: parse_size(size, MAX_SIZE).filter(|size| *size > 0).unwrap_or(DEFAULT_SIZE),
+            color: Color::from(color).unwrap_or(Color(0, 0, 0, 255)),
+            origin: origin.as_deref().map(|origin| Origin::from(origin.trim())).unwrap_or(Origin::BottomCenter),
+            padding: parse_size(padding, MAX_PADDING).unwrap_or(0),
+            background: Color::from(background).filter(|background| !background.is_transparent())
+        })
+
+    }
+
+    pub fn scale(self, dpr: f32) -> Text {
+        Text {
+            size: (self.size as f32 * dpr).round().max(1.0) as u16,
+            padding: (self.padding as f32 * dpr).round() as u16,
+            ..self
+        }
+    }
+}
+
+// Only font family names are accepted, styles and sizes of Pango font descriptions are not
+fn parse_font(value: &Option<String>) -> Option<String> {
+
+    let value = value.as_ref()?.trim();
+
+    if value.is_empty() || value.len() > MAX_FONT_LENGTH || !value.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-' || c == '_') {
+        return None;
+    }
+
+    // Family must not end with a size or a style, e.g. "Sans Bold 200"
+    let last = value.split_whitespace().last()?.to_ascii_lowercase();
+
+    if last.trim_end_matches("px").parse::<f64>().is_ok() || FONT_STYLES.contains(&last.as_str()) {
+        return None;
+    }
+
+    Some(value.to_string())
+
+}
+
+fn parse_size(value: &Option<String>, max: u16) -> Option<u16> {
+    value.as_ref()?.trim().parse::<u16>().ok().map(|value| value.min(max))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+
+    #[test]
+    fn test_text_from() {
+        assert_eq!(Text::from(&None, &value("Roboto"), &value("12"), &None, &None, &None, &None), None);
+        assert_eq!(Text::from(&value("  "), &None, &None, &None, &None, &None, &None), None);
+
+        assert_eq!(Text::from(&value("SAMPLE"), &None, &None, &None, &None, &None, &None), Some(Text {
+            content: "SAMPLE".to_string(),
+            font: None,
+            size: 24,
+            color: Color(0, 0, 0, 255),
+            origin: Origin::BottomCenter,
+            padding: 0,
+            background: None
+        }));
+
+        assert_eq!(Text::from(&value("$ 19.99"), &value("DejaVu Sans"), &value("32"), &value("white"), &value("top-left"), &value("8"), &value("rgba(0,0,0,0.5)")), Some(Text {
+            content: "$ 19.99".to_string(),
+            font: Some("DejaVu Sans".to_string()),
+            size: 32,
+            color: Color(255, 255, 255, 255),
+            origin: Origin::TopLeft,
+            padding: 8,
+            background: Some(Color(0, 0, 0, 128))
+        }));
+    }
+
+    #[test]
+    fn test_text_limits() {
+        let text = Text::from(&value(&"a".repeat(1000)), &value("Sans Bold 200"), &value("10000"), &value("invalid"), &None, &value("-1"), &value("transparent")).unwrap();
+        assert_eq!(text.content.len(), 256);
+        assert_eq!(text.font, None);
+        assert_eq!(text.size, 512);
+        assert_eq!(text.color, Color(0, 0, 0, 255));
+        assert_eq!(text.padding, 0);
+        assert_eq!(text.background, None);
+
+        assert_eq!(parse_font(&value("Sans Bold")), None);
+        assert_eq!(parse_font(&value("Sans 12px")), None);
+        assert_eq!(parse_font(&value("Sans italic")), None);
+        assert_eq!(parse_font(&value("Black Ops One")), Some("Black Ops One".to_string()));
+        assert_eq!(parse_font(&value("DejaVu Sans Mono")), Some("DejaVu Sans Mono".to_string()));
+        assert_eq!(Text::from(&value("a"), &value("Sans, Serif"), &value("0"), &None, &None, &None, &None).map(|text| (text.font, text.size)), Some((None, 24)));
+        assert_eq!(Text::from(&value("a"), &None, &value("20"), &None, &None, &value("4"), &None).map(|text| text.scale(2.0)).map(|text| (text.size, text.padding)), Some((40, 8)));
+    }
+}
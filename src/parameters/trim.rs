@@ -0,0 +1,58 @@
+use serde::Serialize;
+use crate::parameters::color::Color;
+
+const DEFAULT_THRESHOLD: f64 = 10.0;
+const MAX_THRESHOLD: f64 = 255.0;
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Trim {
+    pub threshold: f64,
+    pub color: Option<Color>
+}
+
+impl Trim {
+    pub fn from(value: &Option<String>) -> Option<Trim> {
+
+        // Format: trim=true or trim=threshold[,color], color defaults to top-left pixel or transparency
+        let value = match value {
+            Some(value) => value.trim(),
+            None => return None
+        };
+
+        if value == "false" {
+            return None;
+        }
+
+        if value == "true" {
+            return Some(Trim { threshold: DEFAULT_THRESHOLD, color: None });
+        }
+
+        let (threshold, color) = match value.split_once(',') {
+            Some((threshold, color)) => (threshold, Some(Color::from(&Some(color.to_string()))?)),
+            None => (value, None)
+        };
+
+        let threshold = threshold.parse::<f64>().ok().filter(|threshold| *threshold >= 0.0)?.min(MAX_THRESHOLD);
+
+        Some(Trim { threshold, color })
+
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trim_from() {
+        assert_eq!(Trim::from(&None), None);
+        assert_eq!(Trim::from(&Some("false".to_string())), None);
+        assert_eq!(Trim::from(&Some("true".to_string())), Some(Trim { threshold: 10.0, color: None }));
+        assert_eq!(Trim::from(&Some("25".to_string())), Some(Trim { threshold: 25.0, color: None }));
+        assert_eq!(Trim::from(&Some("1000".to_string())), Some(Trim { threshold: 255.0, color: None }));
+        assert_eq!(Trim::from(&Some("5,white".to_string())), Some(Trim { threshold: 5.0, color: Some(Color(255, 255, 255, 255)) }));
+        assert_eq!(Trim::from(&Some("5,255,0,0".to_string())), Some(Trim { threshold: 5.0, color: Some(Color(255, 0, 0, 255)) }));
+        assert_eq!(Trim::from(&Some("5,invalid".to_string())), None);
+        assert_eq!(Trim::from(&Some("-1".to_string())), None);
+    }
+}
@@ -0,0 +1,94 @@
+use serde::Serialize;
+use crate::parameters::origin::Origin;
+
+const MAX_SCALE: f64 = 1.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Watermark {
+    pub image: String,
+    pub origin: Origin,
+    pub offset: (i32, i32),
+    pub scale: Option<f64>,
+    pub opacity: f64,
+    pub tile: bool
+}
+
+impl Watermark {
+    pub fn from(value: &Option<String>) -> Option<Watermark> {
+
+        // Format: wm=image[,origin[,x,y[,scale[,opacity[,tile]]]]], image is a file name in the watermark directory
+        let value = match value {
+            Some(value) => value,
+            None => return None
+        };
+
+        let parts: Vec<&str> = value.split(',').map(|part| part.trim()).collect();
+
+        let image = parts[0];
+
+        if image.is_empty() || image.starts_with('.') || !image.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.') {
+            return None;
+        }
+
+        let origin = match parts.get(1) {
+            Some(origin) if !origin.is_empty() => Origin::from(*origin),
+            _ => Origin::BottomRight
+        };
+
+        let offset_x = parts.get(2).and_then(|x| x.parse::<i32>().ok()).unwrap_or(0);
+        let offset_y = parts.get(3).and_then(|y| y.parse::<i32>().ok()).unwrap_or(0);
+
+        let scale = parts.get(4)
+            .and_then(|scale| scale.parse::<f64>().ok())
+            .filter(|scale| scale.is_finite() && *scale > 0.0)
+            .map(|scale| scale.min(MAX_SCALE));
+
+        let opacity = parts.get(5)
+            .and_then(|opacity| opacity.parse::<f64>().ok())
+            .filter(|opacity| opacity.is_finite())
+            .map(|opacity| opacity.clamp(0.0, 1.0))
+            .unwrap_or(1.0);
+
+        let tile = parts.get(6) == Some(&"tile");
+
+        Some(Watermark {
+            image: image.to_string(),
+            origin,
+            offset: (offset_x, offset_y),
+            scale,
+            opacity,
+            tile
+        })
+
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watermark_from() {
+        assert_eq!(Watermark::from(&None), None);
+        assert_eq!(Watermark::from(&Some("".to_string())), None);
+        assert_eq!(Watermark::from(&Some("../secret.png".to_string())), None);
+        assert_eq!(Watermark::from(&Some("sub/logo.png".to_string())), None);
+        assert_eq!(Watermark::from(&Some("logo.png".to_string())), Some(Watermark {
+            image: "logo.png".to_string(),
+            origin: Origin::BottomRight,
+            offset: (0, 0),
+            scale: None,
+            opacity: 1.0,
+            tile: false
+        }));
+        assert_eq!(Watermark::from(&Some("logo.png,top-left,10,-5,0.25,0.5,tile".to_string())), Some(Watermark {
+            image: "logo.png".to_string(),
+            origin: Origin::TopLeft,
+            offset: (10, -5),
+            scale: Some(0.25),
+            opacity: 0.5,
+            tile: true
+        }));
+        assert_eq!(Watermark::from(&Some("logo.png,center,0,0,5,2".to_string())).map(|watermark| (watermark.scale, watermark.opacity)), Some((Some(1.0), 1.0)));
+    }
+}
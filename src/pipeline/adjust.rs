@@ -0,0 +1,135 @@
+use libvips::{ops, VipsImage};
+use libvips::ops::{BandFormat, ExtractBandOptions, Interpretation, OperationMath2};
+use log::debug;
+
+use crate::parameters::{Adjustments, Auto, UrlParameters};
+use crate::pipeline::{check, PipelineResult};
+
+const CONTEXT: &str = "adjust image";
+
+// Percentage of darkest and brightest pixels clipped by automatic levels
+const LEVELS_CLIP: f64 = 0.5;
+const NORMALIZE_CLIP: f64 = 1.0;
+
+pub(crate) async fn run(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
+
+    let adjustments = &url_parameters.adjustments;
+
+    let high_depth = matches!(image.get_format(), Ok(BandFormat::Ushort));
+    let (format, interpretation, max) = match high_depth {
+        true => (BandFormat::Ushort, Interpretation::Rgb16, 65535.0),
+        false => (BandFormat::Uchar, Interpretation::Srgb, 255.0)
+    };
+
+    // Alpha channel is kept aside and joined back unchanged
+    let (image, alpha) = split_alpha(image)?;
+    let mut image = check(ops::colourspace(&image, interpretation), CONTEXT)?;
+
+    if let Some(auto) = adjustments.auto {
+        debug!("Applying automatic {auto:?}");
+        image = match auto {
+            Auto::Levels => levels(&image, max)?,
+            Auto::Normalize => normalize(&image, interpretation)?
+        };
+    }
+
+    if adjustments.modulates() {
+        debug!("Modulating brightness, contrast, saturation and hue");
+        image = modulate(&image, adjustments, interpretation)?;
+    }
+
+    if adjustments.gamma != 1.0 {
+        debug!("Applying gamma {}", adjustments.gamma);
+        image = gamma(&image, adjustments.gamma, max)?;
+    }
+
+    let image = check(ops::cast(&image, format), CONTEXT)?;
+
+    match alpha {
+        Some(alpha) => check(ops::bandjoin(&mut [image, alpha]), CONTEXT),
+        None => Ok(image)
+    }
+
+}
+
+// Separate color bands and alpha channel
+pub(crate) fn split_alpha(image: VipsImage) -> PipelineResult<(VipsImage, Option<VipsImage>)> {
+
+    if !image.image_hasalpha() {
+        return Ok((image, None));
+    }
+
+    let bands = image.get_bands();
+
+    let color = check(ops::extract_band_with_opts(&image, 0, &ExtractBandOptions { n: bands - 1 }), CONTEXT)?;
+    let alpha = check(ops::extract_band(&image, bands - 1), CONTEXT)?;
+
+    Ok((color, Some(alpha)))
+
+}
+
+// Stretch each channel separately to the full range
+fn levels(image: &VipsImage, max: f64) -> PipelineResult<VipsImage> {
+
+    let mut a = Vec::with_capacity(image.get_bands() as usize);
+    let mut b = Vec::with_capacity(image.get_bands() as usize);
+
+    for band in 0..image.get_bands() {
+        let band = check(ops::extract_band(image, band), CONTEXT)?;
+        let low = check(ops::percent(&band, LEVELS_CLIP), CONTEXT)? as f64;
+        let high = check(ops::percent(&band, 100.0 - LEVELS_CLIP), CONTEXT)? as f64;
+
+        let scale = if high > low { max / (high - low) } else { 1.0 };
+        a.push(scale);
+        b.push(if high > low { -low * scale } else { 0.0 });
+    }
+
+    check(ops::linear(image, &mut a, &mut b), CONTEXT)
+
+}
+
+// Stretch lightness to the full range, keeping hue and chroma
+fn normalize(image: &VipsImage, interpretation: Interpretation) -> PipelineResult<VipsImage> {
+
+    let lab = check(ops::colourspace(image, Interpretation::Lab), CONTEXT)?;
+
+    // Percentiles are computed on 8-bit lightness histogram
+    let lightness = check(ops::cast(&check(ops::linear(&check(ops::extract_band(&lab, 0), CONTEXT)?, &mut [2.55], &mut [0.0]), CONTEXT)?, BandFormat::Uchar), CONTEXT)?;
+    let low = check(ops::percent(&lightness, NORMALIZE_CLIP), CONTEXT)? as f64 / 2.55;
+    let high = check(ops::percent(&lightness, 100.0 - NORMALIZE_CLIP), CONTEXT)? as f64 / 2.55;
+
+    if high <= low {
+        return check(ops::copy(image), CONTEXT);
+    }
+
+    let scale = 100.0 / (high - low);
+    let lab = check(ops::linear(&lab, &mut [scale, 1.0, 1.0], &mut [-low * scale, 0.0, 0.0]), CONTEXT)?;
+
+    check(ops::colourspace(&lab, interpretation), CONTEXT)
+
+}
+
+// Brightness and contrast scale lightness around its middle, saturation scales chroma, hue rotates in degrees
+fn modulate(image: &VipsImage, adjustments: &Adjustments, interpretation: Interpretation) -> PipelineResult<VipsImage> {
+
+    let lch = check(ops::colourspace(image, Interpretation::Lch), CONTEXT)?;
+
+    let lch = check(ops::linear(
+        &lch,
+        &mut [adjustments.brightness * adjustments.contrast, adjustments.saturation, 1.0],
+        &mut [50.0 * (1.0 - adjustments.contrast), 0.0, adjustments.hue]
+    ), CONTEXT)?;
+
+    check(ops::colourspace(&lch, interpretation), CONTEXT)
+
+}
+
+// Values above 1 brighten midtones, below 1 darken them
+fn gamma(image: &VipsImage, gamma: f64, max: f64) -> PipelineResult<VipsImage> {
+
+    let normalized = check(ops::linear(image, &mut [1.0 / max], &mut [0.0]), CONTEXT)?;
+    let corrected = check(ops::math_2_const(&normalized, OperationMath2::Pow, &mut [1.0 / gamma]), CONTEXT)?;
+
+    check(ops::linear(&corrected, &mut [max], &mut [0.0]), CONTEXT)
+
+}
@@ -1,20 +1,242 @@
 use libvips::{ops, VipsImage};
-use crate::parameters::UrlParameters;
-use crate::pipeline::{PipelineError, PipelineResult};
+use std::env;
+
+use libvips::ops::{BandFormat, BlendMode, Composite2Options, CopyOptions, EmbedOptions, Extend, FlattenOptions, Interpretation, PremultiplyOptions, UnpremultiplyOptions};
+use crate::parameters::{Background, Color, UrlParameters};
+use crate::pipeline::{check, PipelineError, PipelineResult};
 use crate::services::vips::get_error_message;
 
+const CONTEXT: &str = "render background";
+
 pub(crate) async fn run(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
-    
+
     let background = match &url_parameters.background {
-        Some(background) => Vec::from(background),
+        Some(background) => background,
         None => return Ok(image)
     };
-    
-    let background_image = VipsImage::new_from_image(&image, &background).unwrap();
-    
-    match ops::composite_2(&background_image, &image, ops::BlendMode::Over) {
+
+    let background_image = canvas(&image, background, image.get_width(), image.get_height())?;
+
+    // Keep 16-bit images in 16-bit compositing space
+    let high_depth = matches!(image.get_format(), Ok(BandFormat::Ushort));
+
+    match ops::composite_2_with_opts(&background_image, &image, BlendMode::Over, &Composite2Options {
+        compositing_space: if high_depth { Interpretation::Rgb16 } else { Interpretation::Srgb },
+        ..Composite2Options::default()
+    }) {
         Ok(image) => Ok(image),
         Err(_) => Err(PipelineError(format!("Failed to embed background: {}", get_error_message())))
     }
 
-}
\ No newline at end of file
+}
+
+// Render background of given size in the color space and depth of the image
+pub(crate) fn canvas(image: &VipsImage, background: &Background, width: i32, height: i32) -> PipelineResult<VipsImage> {
+
+    let high_depth = matches!(image.get_format(), Ok(BandFormat::Ushort));
+
+    let canvas = match background {
+        Background::Color(color) => {
+            let black = check(ops::black(width, height), CONTEXT)?;
+            colorize(&black, color, color)?
+        },
+        Background::Linear { from, to, angle } => colorize(&linear_gradient(width, height, *angle)?, from, to)?,
+        Background::Radial { inner, outer } => colorize(&radial_gradient(width, height)?, inner, outer)?,
+        Background::Ambient { sigma } => return ambient(image, width, height, *sigma, high_depth)
+    };
+
+    // Gradients are rendered in 8-bit range as floats
+    let canvas = match high_depth {
+        true => check(ops::cast(&check(ops::linear(&canvas, &mut [257.0], &mut [0.0]), CONTEXT)?, BandFormat::Ushort), CONTEXT)?,
+        false => check(ops::cast(&canvas, BandFormat::Uchar), CONTEXT)?
+    };
+
+    check(ops::copy_with_opts(&canvas, &CopyOptions {
+        interpretation: if high_depth { Interpretation::Rgb16 } else { Interpretation::Srgb },
+        ..CopyOptions::default()
+    }), CONTEXT)
+
+}
+
+// Remove alpha channel for formats without transparency, using background color or default matte
+pub(crate) async fn flatten(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
+
+    let matte = match url_parameters.background.as_ref().and_then(|background| background.get_color()) {
+        Some(color) if !color.is_transparent() => color.clone(),
+        _ => get_default_matte()
+    };
+
+    let high_depth = matches!(image.get_format(), Ok(BandFormat::Ushort));
+    let scale = if high_depth { 257.0 } else { 1.0 };
+
+    match ops::flatten_with_opts(&image, &FlattenOptions {
+        background: Vec::from(&matte)[0..3].iter().map(|value| value * scale).collect(),
+        max_alpha: 255.0 * scale
+    }) {
+        Ok(image) => Ok(image),
+        Err(_) => Err(PipelineError(format!("Failed to flatten image: {}", get_error_message())))
+    }
+
+}
+
+// Convert image to RGB with alpha channel, keeping its depth
+pub(crate) fn add_alpha(image: VipsImage) -> PipelineResult<VipsImage> {
+
+    let high_depth = matches!(image.get_format(), Ok(BandFormat::Ushort));
+
+    let image = check(ops::colourspace(&image, if high_depth { Interpretation::Rgb16 } else { Interpretation::Srgb }), CONTEXT)?;
+
+    match image.image_hasalpha() {
+        true => Ok(image),
+        false => check(ops::bandjoin_const(&image, &mut [if high_depth { 65535.0 } else { 255.0 }]), CONTEXT)
+    }
+
+}
+
+// Run operation on colors premultiplied by alpha, so that transparent pixels do not bleed into edges as dark fringes
+pub(crate) fn premultiplied<F>(image: &VipsImage, operation: F) -> PipelineResult<VipsImage>
+    where F: FnOnce(&VipsImage) -> PipelineResult<VipsImage> {
+
+    if !image.image_hasalpha() {
+        return operation(image);
+    }
+
+    let format = check(image.get_format(), "read image format")?;
+    let max_alpha = if matches!(format, BandFormat::Ushort) { 65535.0 } else { 255.0 };
+
+    let image = check(ops::premultiply_with_opts(image, &PremultiplyOptions { max_alpha }), "premultiply image")?;
+    let image = operation(&image)?;
+
+    let image = check(ops::unpremultiply_with_opts(&image, &UnpremultiplyOptions {
+        max_alpha,
+        ..UnpremultiplyOptions::default()
+    }), "unpremultiply image")?;
+
+    // Premultiplied images are floats
+    check(ops::cast(&image, format), "unpremultiply image")
+
+}
+
+fn get_default_matte() -> Color {
+    Color::from(&env::var("DEFAULT_MATTE").ok()).unwrap_or(Color(255, 255, 255, 255))
+}
+
+// Interpolate between two RGBA colors using single band image with values 0-1
+fn colorize(t: &VipsImage, from: &Color, to: &Color) -> PipelineResult<VipsImage> {
+
+    let from = Vec::from(from);
+    let to = Vec::from(to);
+
+    let mut a: Vec<f64> = from.iter().zip(&to).map(|(from, to)| to - from).collect();
+    let mut b = from;
+
+    check(ops::linear(t, &mut a, &mut b), CONTEXT)
+
+}
+
+// Position along the gradient line, angle in CSS convention (0 = bottom to top, 90 = left to right)
+fn linear_gradient(width: i32, height: i32, angle: f64) -> PipelineResult<VipsImage> {
+
+    let (dx, dy) = (angle.to_radians().sin(), -angle.to_radians().cos());
+    let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+    let length = (width as f64 * dx).abs() + (height as f64 * dy).abs();
+
+    let xyz = check(ops::xyz(width, height), CONTEXT)?;
+    let x = check(ops::linear(&check(ops::extract_band(&xyz, 0), CONTEXT)?, &mut [dx / length], &mut [0.5 - (cx * dx + cy * dy) / length]), CONTEXT)?;
+    let y = check(ops::linear(&check(ops::extract_band(&xyz, 1), CONTEXT)?, &mut [dy / length], &mut [0.0]), CONTEXT)?;
+
+    check(ops::add(&x, &y), CONTEXT)
+
+}
+
+// Distance from center relative to distance of corners
+fn radial_gradient(width: i32, height: i32) -> PipelineResult<VipsImage> {
+
+    let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+    let radius = (cx * cx + cy * cy).sqrt();
+
+    let xyz = check(ops::xyz(width, height), CONTEXT)?;
+    let centered = check(ops::linear(&xyz, &mut [1.0, 1.0], &mut [-cx, -cy]), CONTEXT)?;
+    let squared = check(ops::multiply(&centered, &centered), CONTEXT)?;
+    let distance_squared = check(ops::linear(&check(ops::bandmean(&squared), CONTEXT)?, &mut [2.0], &mut [0.0]), CONTEXT)?;
+    let distance = check(ops::math_2_const(&distance_squared, ops::OperationMath2::Pow, &mut [0.5]), CONTEXT)?;
+
+    check(ops::linear(&distance, &mut [1.0 / radius], &mut [0.0]), CONTEXT)
+
+}
+
+// Blurred copy of the image covering the whole canvas
+fn ambient(image: &VipsImage, width: i32, height: i32, sigma: f64, high_depth: bool) -> PipelineResult<VipsImage> {
+
+    let scale = if high_depth { 257.0 } else { 1.0 };
+    let interpretation = if high_depth { Interpretation::Rgb16 } else { Interpretation::Srgb };
+
+    let copy = match image.image_hasalpha() {
+        true => check(ops::flatten_with_opts(image, &FlattenOptions {
+            background: Vec::from(&get_default_matte())[0..3].iter().map(|value| value * scale).collect(),
+            max_alpha: 255.0 * scale
+        }), CONTEXT)?,
+        false => check(ops::copy(image), CONTEXT)?
+    };
+
+    let copy = check(ops::colourspace(&copy, interpretation), CONTEXT)?;
+
+    let factor = (width as f64 / copy.get_width() as f64).max(height as f64 / copy.get_height() as f64);
+    let copy = check(ops::resize(&copy, factor), CONTEXT)?;
+
+    // Center and crop, edges are repeated to compensate rounding of resized dimensions
+    let left = (width - copy.get_width()) / 2;
+    let top = (height - copy.get_height()) / 2;
+    let copy = check(ops::embed_with_opts(&copy, left, top, width, height, &EmbedOptions {
+        extend: Extend::Copy,
+        ..EmbedOptions::default()
+    }), CONTEXT)?;
+
+    let blurred = check(ops::gaussblur(&copy, sigma), CONTEXT)?;
+
+    check(ops::bandjoin_const(&blurred, &mut [255.0 * scale]), CONTEXT)
+
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::{get_url_parameters, init_vips};
+
+    use super::*;
+
+    // Opaque red pixel next to a transparent green one
+    fn get_image() -> VipsImage {
+        VipsImage::new_from_memory(&[255, 0, 0, 255, 0, 255, 0, 0], 2, 1, 4, BandFormat::Uchar).unwrap()
+    }
+
+    async fn get_pixels(query: &str) -> (Vec<f64>, Vec<f64>) {
+        init_vips();
+
+        let image = flatten(get_image(), &get_url_parameters("data/flatten.png", query)).await.unwrap();
+
+        assert_eq!(image.get_bands(), 3);
+        (ops::getpoint(&image, 0, 0).unwrap(), ops::getpoint(&image, 1, 0).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn test_flatten() {
+        assert_eq!(get_pixels("bg=blue").await, (vec![255.0, 0.0, 0.0], vec![0.0, 0.0, 255.0]));
+
+        // Transparent background falls back to the default matte
+        let matte = Vec::from(&get_default_matte())[0..3].to_vec();
+        assert_eq!(get_pixels("bg=transparent").await, (vec![255.0, 0.0, 0.0], matte.clone()));
+        assert_eq!(get_pixels("").await, (vec![255.0, 0.0, 0.0], matte));
+    }
+
+    #[test]
+    fn test_premultiplied() {
+        init_vips();
+
+        // Transparent green pixel does not bleed into the red one
+        let image = premultiplied(&get_image(), |image| check(ops::gaussblur(image, 1.0), CONTEXT)).unwrap();
+        let pixel = ops::getpoint(&image, 0, 0).unwrap();
+
+        assert!(matches!(image.get_format(), Ok(BandFormat::Uchar)));
+        assert!(pixel[0] > 250.0 && pixel[1] < 5.0, "{pixel:?}");
+    }
+}
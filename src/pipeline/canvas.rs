@@ -0,0 +1,68 @@
+use libvips::{ops, VipsImage};
+use libvips::ops::{BandFormat, BlendMode, Composite2Options, Interpretation, Size, ThumbnailImageOptions};
+use log::debug;
+
+use crate::parameters::{Background, Color, UrlParameters};
+use crate::pipeline::{background, check, PipelineResult};
+
+const CONTEXT: &str = "extend image";
+
+pub(crate) async fn run(mut image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
+
+    if let Some(padding) = &url_parameters.padding {
+        let (width, height) = (image.get_width(), image.get_height());
+        let (top, right, bottom, left) = padding.resolve(width, height);
+
+        debug!("Padding image by {top},{right},{bottom},{left}");
+        image = extend(image, url_parameters, (width + left + right, height + top + bottom), (left, top))?;
+    }
+
+    if let Some(canvas) = &url_parameters.canvas {
+        let (width, height) = (canvas.width as i32, canvas.height as i32);
+
+        // Larger images are downscaled to fit within the canvas
+        if image.get_width() > width || image.get_height() > height {
+            image = fit(image, width, height)?;
+        }
+
+        let position = canvas.origin.position((width, height), (image.get_width(), image.get_height()));
+
+        debug!("Placing image on {width}x{height} canvas");
+        image = extend(image, url_parameters, (width, height), position)?;
+    }
+
+    Ok(image)
+
+}
+
+// Place image on canvas filled with background, or transparent if background is not set
+fn extend(image: VipsImage, url_parameters: &UrlParameters<'_>, (width, height): (i32, i32), (x, y): (i32, i32)) -> PipelineResult<VipsImage> {
+
+    let high_depth = matches!(image.get_format(), Ok(BandFormat::Ushort));
+    let interpretation = if high_depth { Interpretation::Rgb16 } else { Interpretation::Srgb };
+
+    let image = background::add_alpha(image)?;
+
+    let background = match &url_parameters.background {
+        Some(background) => background.clone(),
+        None => Background::Color(Color(0, 0, 0, 0))
+    };
+
+    let canvas = background::canvas(&image, &background, width, height)?;
+
+    check(ops::composite_2_with_opts(&canvas, &image, BlendMode::Over, &Composite2Options {
+        x,
+        y,
+        compositing_space: interpretation,
+        ..Composite2Options::default()
+    }), CONTEXT)
+
+}
+
+fn fit(image: VipsImage, width: i32, height: i32) -> PipelineResult<VipsImage> {
+    check(ops::thumbnail_image_with_opts(&image, width, &ThumbnailImageOptions {
+        height,
+        size: Size::Down,
+        ..ThumbnailImageOptions::default()
+    }), CONTEXT)
+}
@@ -0,0 +1,63 @@
+use libvips::{ops, VipsImage};
+use libvips::ops::{EmbedOptions, Extend, Kernel, ResizeOptions, SharpenOptions};
+use log::debug;
+
+use crate::parameters::{Blur, Pixelate, Sharpen, UrlParameters};
+use crate::pipeline::{check, PipelineResult};
+use crate::pipeline::background::premultiplied;
+
+const CONTEXT: &str = "apply effect";
+
+pub(crate) async fn run(mut image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
+
+    if let Some(blur) = &url_parameters.blur {
+        debug!("Blurring image with sigma {}", blur.sigma);
+        image = self::blur(&image, blur)?;
+    }
+
+    if let Some(pixelate) = &url_parameters.pixelate {
+        debug!("Pixelating image with size {}", pixelate.size);
+        image = self::pixelate(&image, pixelate)?;
+    }
+
+    if let Some(sharpen) = &url_parameters.sharpen {
+        debug!("Sharpening image with sigma {}", sharpen.sigma);
+        image = self::sharpen(&image, sharpen)?;
+    }
+
+    Ok(image)
+
+}
+
+pub(crate) fn blur(image: &VipsImage, blur: &Blur) -> PipelineResult<VipsImage> {
+    premultiplied(image, |image| check(ops::gaussblur(image, blur.sigma), CONTEXT))
+}
+
+fn sharpen(image: &VipsImage, sharpen: &Sharpen) -> PipelineResult<VipsImage> {
+    premultiplied(image, |image| check(ops::sharpen_with_opts(image, &SharpenOptions {
+        sigma: sharpen.sigma,
+        m_1: sharpen.flat,
+        m_2: sharpen.jagged,
+        ..SharpenOptions::default()
+    }), CONTEXT))
+}
+
+// Downscale to blocks and upscale back with nearest neighbour, rounded dimensions are restored by repeating edges
+pub(crate) fn pixelate(image: &VipsImage, pixelate: &Pixelate) -> PipelineResult<VipsImage> {
+
+    let (width, height) = (image.get_width(), image.get_height());
+    let size = (pixelate.size as i32).min(width).min(height).max(1);
+
+    let small = premultiplied(image, |image| check(ops::shrink(image, size as f64, size as f64), CONTEXT))?;
+
+    let large = check(ops::resize_with_opts(&small, size as f64, &ResizeOptions {
+        kernel: Kernel::Nearest,
+        ..ResizeOptions::default()
+    }), CONTEXT)?;
+
+    check(ops::embed_with_opts(&large, 0, 0, width, height, &EmbedOptions {
+        extend: Extend::Copy,
+        ..EmbedOptions::default()
+    }), CONTEXT)
+
+}
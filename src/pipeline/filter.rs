@@ -0,0 +1,101 @@
+use libvips::{ops, VipsImage};
+use libvips::ops::{BandFormat, CopyOptions, Interpretation};
+use log::debug;
+
+use crate::parameters::{Color, Filter, UrlParameters};
+use crate::pipeline::adjust::split_alpha;
+use crate::pipeline::{check, PipelineResult};
+
+const CONTEXT: &str = "apply filter";
+
+const SEPIA_MATRIX: [f64; 9] = [
+    0.393, 0.769, 0.189,
+    0.349, 0.686, 0.168,
+    0.272, 0.534, 0.131
+];
+
+pub(crate) async fn run(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
+
+    let filter = match &url_parameters.filter {
+        Some(filter) => filter,
+        None => return Ok(image)
+    };
+
+    let high_depth = matches!(image.get_format(), Ok(BandFormat::Ushort));
+    let (format, interpretation, max) = match high_depth {
+        true => (BandFormat::Ushort, Interpretation::Rgb16, 65535.0),
+        false => (BandFormat::Uchar, Interpretation::Srgb, 255.0)
+    };
+
+    // Alpha channel is kept aside and joined back unchanged
+    let (image, alpha) = split_alpha(image)?;
+    let image = check(ops::colourspace(&image, interpretation), CONTEXT)?;
+
+    debug!("Applying filter {filter:?}");
+
+    let image = match filter {
+        Filter::Grayscale => check(ops::colourspace(&grayscale(&image, high_depth)?, interpretation), CONTEXT)?,
+        Filter::Sepia => check(ops::recomb(&image, &check(VipsImage::image_new_matrix_from_array(3, 3, &SEPIA_MATRIX), CONTEXT)?), CONTEXT)?,
+        Filter::Invert => check(ops::linear(&image, &mut [-1.0], &mut [max]), CONTEXT)?,
+        Filter::Tint(color) => tint(&image, color, interpretation)?,
+        Filter::Duotone(shadows, highlights) => duotone(&image, shadows, highlights, max)?
+    };
+
+    // Output is always sRGB in the depth of the input
+    let image = check(ops::cast(&image, format), CONTEXT)?;
+    let image = check(ops::copy_with_opts(&image, &CopyOptions {
+        interpretation,
+        ..CopyOptions::default()
+    }), CONTEXT)?;
+
+    match alpha {
+        Some(alpha) => check(ops::bandjoin(&mut [image, alpha]), CONTEXT),
+        None => Ok(image)
+    }
+
+}
+
+fn grayscale(image: &VipsImage, high_depth: bool) -> PipelineResult<VipsImage> {
+    check(ops::colourspace(image, if high_depth { Interpretation::Grey16 } else { Interpretation::BW }), CONTEXT)
+}
+
+// Keep lightness of the image, replace its chroma and hue by the tint color
+fn tint(image: &VipsImage, color: &Color, interpretation: Interpretation) -> PipelineResult<VipsImage> {
+
+    let tint = get_lab(color)?;
+
+    let lab = check(ops::colourspace(image, Interpretation::Lab), CONTEXT)?;
+    let lab = check(ops::linear(&lab, &mut [1.0, 0.0, 0.0], &mut [0.0, tint[1], tint[2]]), CONTEXT)?;
+
+    check(ops::colourspace(&lab, interpretation), CONTEXT)
+
+}
+
+// Map lightness to gradient between shadows and highlights colors
+fn duotone(image: &VipsImage, shadows: &Color, highlights: &Color, max: f64) -> PipelineResult<VipsImage> {
+
+    let lightness = check(ops::extract_band(&check(ops::colourspace(image, Interpretation::Lab), CONTEXT)?, 0), CONTEXT)?;
+
+    // Lightness is in range 0-100, colors are scaled to depth of the image
+    let scale = max / 255.0 / 100.0;
+    let shadows = Vec::from(shadows);
+    let highlights = Vec::from(highlights);
+
+    let mut a: Vec<f64> = (0..3).map(|band| (highlights[band] - shadows[band]) * scale).collect();
+    let mut b: Vec<f64> = (0..3).map(|band| shadows[band] * max / 255.0).collect();
+
+    check(ops::linear(&lightness, &mut a, &mut b), CONTEXT)
+
+}
+
+fn get_lab(color: &Color) -> PipelineResult<Vec<f64>> {
+
+    let pixel = check(ops::linear(&check(ops::black(1, 1), CONTEXT)?, &mut [0.0], &mut Vec::from(color)[0..3].to_vec()), CONTEXT)?;
+    let pixel = check(ops::copy_with_opts(&check(ops::cast(&pixel, BandFormat::Uchar), CONTEXT)?, &CopyOptions {
+        interpretation: Interpretation::Srgb,
+        ..CopyOptions::default()
+    }), CONTEXT)?;
+
+    check(ops::getpoint(&check(ops::colourspace(&pixel, Interpretation::Lab), CONTEXT)?, 0, 0), CONTEXT)
+
+}
@@ -1,44 +1,86 @@
+use std::env;
 use std::path::PathBuf;
 
-use libvips::ops::{ForeignHeifCompression, ForeignHeifEncoder, ForeignKeep, ForeignSubsample, ForeignWebpPreset, HeifsaveOptions, JpegsaveOptions, PngsaveOptions, WebpsaveOptions};
+use libvips::ops::{ForeignHeifCompression, ForeignKeep, ForeignSubsample, ForeignTiffCompression, ForeignTiffPredictor, ForeignTiffResunit, ForeignWebpPreset, GifsaveOptions, Interpretation, HeifsaveBufferOptions, HeifsaveOptions, JpegsaveBufferOptions, JpegsaveOptions, PngsaveOptions, TiffsaveOptions, WebpsaveBufferOptions, WebpsaveOptions};
 use libvips::{ops, VipsImage};
 use log::{debug, error};
 
 use crate::cache;
-use crate::parameters::{Quality, UrlParameters};
-use crate::pipeline::{PipelineError, PipelineResult};
-use crate::services::formats::OutputFormat;
+use crate::parameters::{Quality, Subsample, UrlParameters};
+use crate::pipeline::{metadata, quality, PipelineError, PipelineResult};
+use crate::services::capabilities;
+use crate::services::formats::{get_output_depth, requires_bigtiff, OutputFormat};
+use crate::services::profiles;
+use crate::services::profiles::EncoderProfile;
 use crate::services::vips::get_error_message;
 
-pub(crate) async fn run(image: VipsImage, url_parameters: &UrlParameters<'_>, output_format: &OutputFormat) -> PipelineResult<PathBuf> {
+pub(crate) async fn run(image: VipsImage, url_parameters: &UrlParameters<'_>, output_format: &OutputFormat, page_height: i32) -> PipelineResult<PathBuf> {
+
+    let image = match get_output_depth(output_format, url_parameters.depth) {
+        8 => reduce_depth(image)?,
+        _ => image
+    };
+
     match output_format {
-        OutputFormat::Avif => finalize_avif(image, url_parameters),
-        OutputFormat::Webp => finalize_webp(image, url_parameters),
+        OutputFormat::Avif => finalize_avif(image, url_parameters, page_height),
+        OutputFormat::Webp => finalize_webp(image, url_parameters, page_height),
         OutputFormat::Png => finalize_png(image, url_parameters),
+        OutputFormat::Jxl => finalize_jxl(image, url_parameters),
+        OutputFormat::Gif => finalize_gif(image, url_parameters, page_height),
+        OutputFormat::Tiff => finalize_tiff(image, url_parameters),
+        OutputFormat::Heic => finalize_heic(image, url_parameters),
         _ => finalize_jpg(image, url_parameters)
     }
+
 }
 
-fn finalize_avif(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<PathBuf> {
+// Scale 16-bit image to 8 bits for formats not supporting higher bit depth, HDR transfer functions (PQ, HLG) are not tone-mapped
+fn reduce_depth(image: VipsImage) -> PipelineResult<VipsImage> {
+
+    let interpretation = match image.get_interpretation() {
+        Ok(Interpretation::Rgb16) => Interpretation::Srgb,
+        Ok(Interpretation::Grey16) => Interpretation::BW,
+        _ => return Ok(image)
+    };
+
+    debug!("Reducing image bit depth to 8-bit");
+
+    match ops::colourspace(&image, interpretation) {
+        Ok(image) => Ok(image),
+        Err(_) => Err(PipelineError(format!("Failed to reduce image bit depth: {}", get_error_message())))
+    }
+
+}
+
+fn finalize_avif(image: VipsImage, url_parameters: &UrlParameters<'_>, page_height: i32) -> PipelineResult<PathBuf> {
 
     let cache_path = cache::get_path_from_url_parameters(url_parameters, &OutputFormat::Avif);
+    let profile = &profiles::get(url_parameters).avif;
+    let depth = get_output_depth(&OutputFormat::Avif, url_parameters.depth) as i32;
+
+    let options = HeifsaveOptions {
+        bitdepth: if depth > 8 { depth } else { profile.bitdepth.unwrap_or(8) },
+        compression: ForeignHeifCompression::Av1,
+        effort: profile.effort.unwrap_or(1),
+        lossless: url_parameters.lossless,
+        subsample_mode: subsample_mode(url_parameters, profile, ForeignSubsample::Off),
+        encoder: capabilities::get().get_heif_encoder(profile.encoder.as_deref(), ForeignHeifCompression::Av1),
+        keep: metadata::keep(url_parameters),
+        page_height,
+        ..HeifsaveOptions::default()
+    };
+
+    let quality = match url_parameters.quality {
+        Quality::Custom(quality) => quality as i32,
+        Quality::Default => avif_default_quality(&image, profile),
+        Quality::Target(target) => quality::search(&image, url_parameters, &cache_path, page_height, target, |image, q| {
+            ops::heifsave_buffer_with_opts(image, &heif_buffer_options(&options, q, image.get_height()))
+        })?
+    };
 
     if ops::heifsave_with_opts(&image, &cache_path, &HeifsaveOptions {
-        q: match url_parameters.quality {
-            Quality::Custom(quality) => quality as i32,
-            Quality::Default => avif_default_quality(&image),
-        },
-        bitdepth: 8,
-        compression: ForeignHeifCompression::Hevc,
-        effort: 1,
-        subsample_mode: ForeignSubsample::Off,
-        encoder: ForeignHeifEncoder::Aom,
-        keep: ForeignKeep::None,
-        background: match &url_parameters.background {
-            Some(background) => Vec::from(background)[0..3].to_vec(),
-            None => Vec::new()
-        },
-        ..HeifsaveOptions::default()
+        q: quality,
+        ..options
     }).is_err() {
         error!("Failed to save AVIF image {}: {}", url_parameters.path.to_string_lossy(), get_error_message());
         return Err(PipelineError("Failed to save image".to_string()));
@@ -49,24 +91,35 @@ fn finalize_avif(image: VipsImage, url_parameters: &UrlParameters<'_>) -> Pipeli
 
 }
 
-fn finalize_webp(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<PathBuf> {
+fn finalize_webp(image: VipsImage, url_parameters: &UrlParameters<'_>, page_height: i32) -> PipelineResult<PathBuf> {
 
     let cache_path = cache::get_path_from_url_parameters(url_parameters, &OutputFormat::Webp);
+    let profile = &profiles::get(url_parameters).webp;
 
-    if ops::webpsave_with_opts(&image, &cache_path, &WebpsaveOptions {
-        q: match url_parameters.quality {
-            Quality::Custom(quality) => quality as i32,
-            Quality::Default => webp_default_quality(&image),
-        },
+    let options = WebpsaveOptions {
         preset: ForeignWebpPreset::Last,
+        effort: profile.effort.unwrap_or(4),
+        lossless: url_parameters.lossless,
+        near_lossless: url_parameters.near_lossless,
+        // Lossy WEBP is always 4:2:0, subsample parameter is ignored, sharp RGB to YUV conversion reduces chroma artifacts
         smart_subsample: true,
-        keep: ForeignKeep::None,
-        background: match &url_parameters.background {
-            Some(background) => Vec::from(background)[0..3].to_vec(),
-            None => Vec::new()
-        },
-        alpha_q: 50,
+        keep: metadata::keep(url_parameters),
+        alpha_q: profile.alpha_quality.unwrap_or(50),
+        page_height,
         ..WebpsaveOptions::default()
+    };
+
+    let quality = match url_parameters.quality {
+        Quality::Custom(quality) => quality as i32,
+        Quality::Default => webp_default_quality(&image, profile),
+        Quality::Target(target) => quality::search(&image, url_parameters, &cache_path, page_height, target, |image, q| {
+            ops::webpsave_buffer_with_opts(image, &webp_buffer_options(&options, q, image.get_height()))
+        })?
+    };
+
+    if ops::webpsave_with_opts(&image, &cache_path, &WebpsaveOptions {
+        q: quality,
+        ..options
     }).is_err() {
         error!("Failed to save WEBP image {}: {}", url_parameters.path.to_string_lossy(), get_error_message());
         return Err(PipelineError("Failed to save image".to_string()));
@@ -80,19 +133,27 @@ fn finalize_webp(image: VipsImage, url_parameters: &UrlParameters<'_>) -> Pipeli
 fn finalize_jpg(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<PathBuf> {
 
     let cache_path = cache::get_path_from_url_parameters(url_parameters, &OutputFormat::Jpg);
+    let profile = &profiles::get(url_parameters).jpg;
 
-    if ops::jpegsave_with_opts(&image, &cache_path, &JpegsaveOptions {
-        q: match url_parameters.quality {
-            Quality::Custom(quality) => quality as i32,
-            Quality::Default => jpg_default_quality(&image),
-        },
+    let options = JpegsaveOptions {
         optimize_coding: true,
-        keep: ForeignKeep::None,
-        background: match &url_parameters.background {
-            Some(background) => Vec::from(background)[0..3].to_vec(),
-            None => Vec::new()
-        },
+        interlace: url_parameters.progressive,
+        subsample_mode: subsample_mode(url_parameters, profile, ForeignSubsample::Auto),
+        keep: metadata::keep(url_parameters),
         ..JpegsaveOptions::default()
+    };
+
+    let quality = match url_parameters.quality {
+        Quality::Custom(quality) => quality as i32,
+        Quality::Default => jpg_default_quality(&image, profile),
+        Quality::Target(target) => quality::search(&image, url_parameters, &cache_path, image.get_height(), target, |image, q| {
+            ops::jpegsave_buffer_with_opts(image, &jpeg_buffer_options(&options, q, image.get_height()))
+        })?
+    };
+
+    if ops::jpegsave_with_opts(&image, &cache_path, &JpegsaveOptions {
+        q: quality,
+        ..options
     }).is_err() {
         error!("Failed to save JPG image {}: {}", url_parameters.path.to_string_lossy(), get_error_message());
         return Err(PipelineError("Failed to save image".to_string()));
@@ -106,20 +167,21 @@ fn finalize_jpg(image: VipsImage, url_parameters: &UrlParameters<'_>) -> Pipelin
 fn finalize_png(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<PathBuf> {
 
     let cache_path = cache::get_path_from_url_parameters(url_parameters, &OutputFormat::Png);
+    let profile = &profiles::get(url_parameters).png;
+    let depth = get_output_depth(&OutputFormat::Png, url_parameters.depth) as i32;
     let quality = match url_parameters.quality {
         Quality::Custom(quality) => quality as i32,
-        Quality::Default => 78,
+        Quality::Default | Quality::Target(_) => profile.quality_max.unwrap_or(78),
     };
 
     if ops::pngsave_with_opts(&image, &cache_path, &PngsaveOptions {
-        keep: ForeignKeep::None,
-        palette: true,
+        keep: metadata::keep(url_parameters),
+        palette: url_parameters.palette && !url_parameters.lossless && depth == 8,
+        interlace: url_parameters.progressive,
+        effort: profile.effort.unwrap_or(7),
+        bitdepth: if depth > 8 { depth } else { profile.bitdepth.unwrap_or(8) },
         q: quality,
         dither: if quality < 90 { 0.8 } else { 1.0 },
-        background: match &url_parameters.background {
-            Some(background) => Vec::from(background)[0..3].to_vec(),
-            None => Vec::new()
-        },
         ..PngsaveOptions::default()
     }).is_err() {
         error!("Failed to save PNG image {}: {}", url_parameters.path.to_string_lossy(), get_error_message());
@@ -131,44 +193,329 @@ fn finalize_png(image: VipsImage, url_parameters: &UrlParameters<'_>) -> Pipelin
 
 }
 
-fn avif_default_quality(image: &VipsImage) -> i32 {
+// libvips-rs has no JXL saver bindings, the saver is selected by suffix with options in the option string
+fn finalize_jxl(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<PathBuf> {
+
+    let cache_path = cache::get_path_from_url_parameters(url_parameters, &OutputFormat::Jxl);
+    let profile = &profiles::get(url_parameters).jxl;
+    let effort = profile.effort.unwrap_or_else(|| env::var("JXL_EFFORT").unwrap_or("4".to_string()).parse::<i32>().unwrap_or(4)).clamp(1, 9);
+    let keep = metadata::keep(url_parameters);
+
+    let quality = match url_parameters.quality {
+        Quality::Custom(quality) => quality as i32,
+        Quality::Default => jxl_default_quality(&image, profile),
+        Quality::Target(target) => quality::search(&image, url_parameters, &cache_path, image.get_height(), target, |image, q| {
+            image.image_write_to_buffer(&format!(".jxl{}", jxl_options(q, effort, url_parameters.lossless, keep)))
+        })?
+    };
+
+    let options = jxl_options(quality, effort, url_parameters.lossless || quality >= 100, keep);
+
+    if image.image_write_to_file(&format!("{cache_path}{options}")).is_err() {
+        error!("Failed to save JXL image {}: {}", url_parameters.path.to_string_lossy(), get_error_message());
+        return Err(PipelineError("Failed to save image".to_string()));
+    }
+
+    image.image_set_kill(true);
+    Ok(cache_path.into())
+
+}
+
+fn jxl_options(quality: i32, effort: i32, lossless: bool, keep: ForeignKeep) -> String {
+    format!("[Q={quality},effort={effort},lossless={lossless},keep={}]", keep_nick(keep))
+}
+
+// Nicknames of the libvips keep flags used in option strings
+fn keep_nick(keep: ForeignKeep) -> &'static str {
+    match keep {
+        ForeignKeep::None => "none",
+        ForeignKeep::Exif => "exif",
+        ForeignKeep::Xmp => "xmp",
+        ForeignKeep::Iptc => "iptc",
+        ForeignKeep::Icc => "icc",
+        ForeignKeep::Other => "other",
+        ForeignKeep::All => "all"
+    }
+}
+
+fn finalize_gif(image: VipsImage, url_parameters: &UrlParameters<'_>, page_height: i32) -> PipelineResult<PathBuf> {
+
+    let cache_path = cache::get_path_from_url_parameters(url_parameters, &OutputFormat::Gif);
+    let profile = &profiles::get(url_parameters).gif;
+
+    if ops::gifsave_with_opts(&image, &cache_path, &GifsaveOptions {
+        dither: 1.0,
+        effort: profile.effort.unwrap_or(7),
+        bitdepth: profile.bitdepth.unwrap_or(8),
+        interlace: url_parameters.progressive,
+        keep: metadata::keep(url_parameters),
+        page_height,
+        ..GifsaveOptions::default()
+    }).is_err() {
+        error!("Failed to save GIF image {}: {}", url_parameters.path.to_string_lossy(), get_error_message());
+        return Err(PipelineError("Failed to save image".to_string()));
+    }
+
+    image.image_set_kill(true);
+    Ok(cache_path.into())
+
+}
+
+fn finalize_tiff(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<PathBuf> {
+
+    let cache_path = cache::get_path_from_url_parameters(url_parameters, &OutputFormat::Tiff);
+
+    let compression = match env::var("TIFF_COMPRESSION").unwrap_or("lzw".to_string()).as_str() {
+        "deflate" => ForeignTiffCompression::Deflate,
+        "none" => ForeignTiffCompression::None,
+        _ => ForeignTiffCompression::Lzw
+    };
+
+    // DPI is configured in pixels per inch, libvips expects pixels per millimeter
+    let (xres, yres) = match env::var("TIFF_DPI").ok().and_then(|dpi| dpi.parse::<f64>().ok()) {
+        Some(dpi) => (dpi / 25.4, dpi / 25.4),
+        None => (image.get_xres(), image.get_yres())
+    };
+
+    if ops::tiffsave_with_opts(&image, &cache_path, &TiffsaveOptions {
+        compression,
+        predictor: ForeignTiffPredictor::Horizontal,
+        resunit: ForeignTiffResunit::Inch,
+        xres,
+        yres,
+        bigtiff: requires_bigtiff(&image),
+        keep: metadata::keep(url_parameters),
+        ..TiffsaveOptions::default()
+    }).is_err() {
+        error!("Failed to save TIFF image {}: {}", url_parameters.path.to_string_lossy(), get_error_message());
+        return Err(PipelineError("Failed to save image".to_string()));
+    }
+
+    image.image_set_kill(true);
+    Ok(cache_path.into())
+
+}
+
+fn finalize_heic(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<PathBuf> {
+
+    let cache_path = cache::get_path_from_url_parameters(url_parameters, &OutputFormat::Heic);
+    let profile = &profiles::get(url_parameters).heic;
+    let depth = get_output_depth(&OutputFormat::Heic, url_parameters.depth) as i32;
+
+    let options = HeifsaveOptions {
+        bitdepth: if depth > 8 { depth } else { profile.bitdepth.unwrap_or(8) },
+        compression: ForeignHeifCompression::Hevc,
+        effort: profile.effort.unwrap_or(4),
+        lossless: url_parameters.lossless,
+        subsample_mode: subsample_mode(url_parameters, profile, ForeignSubsample::Auto),
+        encoder: capabilities::get().get_heif_encoder(profile.encoder.as_deref(), ForeignHeifCompression::Hevc),
+        keep: metadata::keep(url_parameters),
+        ..HeifsaveOptions::default()
+    };
+
+    let quality = match url_parameters.quality {
+        Quality::Custom(quality) => quality as i32,
+        Quality::Default => heic_default_quality(&image, profile),
+        Quality::Target(target) => quality::search(&image, url_parameters, &cache_path, image.get_height(), target, |image, q| {
+            ops::heifsave_buffer_with_opts(image, &heif_buffer_options(&options, q, image.get_height()))
+        })?
+    };
+
+    if ops::heifsave_with_opts(&image, &cache_path, &HeifsaveOptions {
+        q: quality,
+        ..options
+    }).is_err() {
+        error!("Failed to save HEIC image {}: {}", url_parameters.path.to_string_lossy(), get_error_message());
+        return Err(PipelineError("Failed to save image".to_string()));
+    }
+
+    image.image_set_kill(true);
+    Ok(cache_path.into())
+
+}
+
+// Buffer savers of libvips-rs take separate option structs with the same fields, used by target quality search
+fn heif_buffer_options(options: &HeifsaveOptions, q: i32, page_height: i32) -> HeifsaveBufferOptions {
+    HeifsaveBufferOptions {
+        q,
+        bitdepth: options.bitdepth,
+        lossless: options.lossless,
+        compression: options.compression,
+        effort: options.effort,
+        subsample_mode: options.subsample_mode,
+        encoder: options.encoder,
+        keep: options.keep,
+        page_height,
+        ..HeifsaveBufferOptions::default()
+    }
+}
+
+fn webp_buffer_options(options: &WebpsaveOptions, q: i32, page_height: i32) -> WebpsaveBufferOptions {
+    WebpsaveBufferOptions {
+        q,
+        lossless: options.lossless,
+        preset: options.preset,
+        smart_subsample: options.smart_subsample,
+        near_lossless: options.near_lossless,
+        alpha_q: options.alpha_q,
+        effort: options.effort,
+        keep: options.keep,
+        page_height,
+        ..WebpsaveBufferOptions::default()
+    }
+}
+
+fn jpeg_buffer_options(options: &JpegsaveOptions, q: i32, page_height: i32) -> JpegsaveBufferOptions {
+    JpegsaveBufferOptions {
+        q,
+        optimize_coding: options.optimize_coding,
+        interlace: options.interlace,
+        subsample_mode: options.subsample_mode,
+        keep: options.keep,
+        page_height,
+        ..JpegsaveBufferOptions::default()
+    }
+}
+
+fn subsample_mode(url_parameters: &UrlParameters<'_>, profile: &EncoderProfile, default: ForeignSubsample) -> ForeignSubsample {
+    match url_parameters.subsample.or(Subsample::from(&profile.subsample)) {
+        Some(Subsample::Auto) => ForeignSubsample::Auto,
+        Some(Subsample::On) => ForeignSubsample::On,
+        Some(Subsample::Off) => ForeignSubsample::Off,
+        None => default
+    }
+}
+
+fn avif_default_quality(image: &VipsImage, profile: &EncoderProfile) -> i32 {
+
+    let width = image.get_width() as f64;
+    let height = image.get_height() as f64;
+    let area = width * height / 1000000.0;
+
+    // Dynamic AVIF quality based on image area, min. 40, max. 59 unless set by encoding profile
+    let (min, max) = (profile.quality_min.unwrap_or(40) as f64, profile.quality_max.unwrap_or(59) as f64);
+    let quality = (8.0 - area).clamp(0.0, 8.0 - 0.25) * (max - min) / (8.0 - 0.25) + min;
+    debug!("Serving image with quality: {}%, {area}MPix", quality as i32);
+
+    quality as i32
+
+}
+
+fn webp_default_quality(image: &VipsImage, profile: &EncoderProfile) -> i32 {
+
+    let width = image.get_width() as f64;
+    let height = image.get_height() as f64;
+    let area = width * height / 1000000.0;
+
+    // Dynamic WebP quality based on image area, min. 16, max. 78 unless set by encoding profile
+    let (min, max) = (profile.quality_min.unwrap_or(16) as f64, profile.quality_max.unwrap_or(78) as f64);
+    let quality = (8.0 - area).clamp(0.0, 8.0 - 0.25) * (max - min) / (8.0 - 0.25) + min;
+    debug!("Serving image with quality: {}%, {area}MPix", quality as i32);
+
+    quality as i32
+
+}
+
+fn jpg_default_quality(image: &VipsImage, profile: &EncoderProfile) -> i32 {
 
     let width = image.get_width() as f64;
     let height = image.get_height() as f64;
     let area = width * height / 1000000.0;
 
-    // Dynamic AVIF quality based on image area, min. 40, max. 59
-    let quality = (8.0 - area).clamp(0.0, 8.0 - 0.25) * (59.0 - 40.0) / (8.0 - 0.25) + 40.0;
+    // Dynamic JPEG quality based on image area, min. 40, max. 75 unless set by encoding profile
+    let (min, max) = (profile.quality_min.unwrap_or(40) as f64, profile.quality_max.unwrap_or(75) as f64);
+    let quality = (8.0 - area).clamp(0.0, 8.0 - 0.25) * (max - min) / (8.0 - 0.25) + min;
     debug!("Serving image with quality: {}%, {area}MPix", quality as i32);
 
     quality as i32
 
 }
 
-fn webp_default_quality(image: &VipsImage) -> i32 {
+fn jxl_default_quality(image: &VipsImage, profile: &EncoderProfile) -> i32 {
 
     let width = image.get_width() as f64;
     let height = image.get_height() as f64;
     let area = width * height / 1000000.0;
 
-    // Dynamic WebP quality based on image area, min. 16, max. 78
-    let quality = (8.0 - area).clamp(0.0, 8.0 - 0.25) * (78.0 - 16.0) / (8.0 - 0.25) + 16.0;
+    // Dynamic JPEG XL quality based on image area, min. 50, max. 80 unless set by encoding profile
+    let (min, max) = (profile.quality_min.unwrap_or(50) as f64, profile.quality_max.unwrap_or(80) as f64);
+    let quality = (8.0 - area).clamp(0.0, 8.0 - 0.25) * (max - min) / (8.0 - 0.25) + min;
     debug!("Serving image with quality: {}%, {area}MPix", quality as i32);
 
     quality as i32
 
 }
 
-fn jpg_default_quality(image: &VipsImage) -> i32 {
+fn heic_default_quality(image: &VipsImage, profile: &EncoderProfile) -> i32 {
 
     let width = image.get_width() as f64;
     let height = image.get_height() as f64;
     let area = width * height / 1000000.0;
 
-    // Dynamic JPEG quality based on image area, min. 40, max. 75
-    let quality = (8.0 - area).clamp(0.0, 8.0 - 0.25) * (75.0 - 40.0) / (8.0 - 0.25) + 40.0;
+    // Dynamic HEIC quality based on image area, min. 45, max. 70 unless set by encoding profile
+    let (min, max) = (profile.quality_min.unwrap_or(45) as f64, profile.quality_max.unwrap_or(70) as f64);
+    let quality = (8.0 - area).clamp(0.0, 8.0 - 0.25) * (max - min) / (8.0 - 0.25) + min;
     debug!("Serving image with quality: {}%, {area}MPix", quality as i32);
 
     quality as i32
 
 }
+
+#[cfg(test)]
+mod tests {
+    use libvips::ops::{BandFormat, BlackOptions, CopyOptions};
+
+    use crate::pipeline::{get_url_parameters, init_vips, resize};
+
+    use super::*;
+
+    fn get_image_16() -> VipsImage {
+        let image = ops::black_with_opts(16, 16, &BlackOptions { bands: 3 }).unwrap();
+        let image = ops::cast(&image, BandFormat::Ushort).unwrap();
+        ops::copy_with_opts(&image, &CopyOptions { interpretation: Interpretation::Rgb16, ..CopyOptions::default() }).unwrap()
+    }
+
+    async fn get_output_format(output_format: &OutputFormat) -> BandFormat {
+        init_vips();
+
+        let url_parameters = get_url_parameters("data/depth-16.png", "w=8&depth=16");
+
+        let image = resize::run(get_image_16(), &url_parameters).await.unwrap();
+        assert!(matches!(image.get_format(), Ok(BandFormat::Ushort)));
+
+        let path = run(image, &url_parameters, output_format, 0).await.unwrap();
+        VipsImage::new_from_file(&path.to_string_lossy()).unwrap().get_format().unwrap()
+    }
+
+    #[actix_web::test]
+    async fn test_finalize_heic() {
+        init_vips();
+
+        // HEIC is disabled without x265 encoder in the installed libheif
+        if !capabilities::get().supports_output(&OutputFormat::Heic) {
+            return;
+        }
+
+        let url_parameters = get_url_parameters("data/finalize.heic", "w=8&depth=10");
+
+        let image = ops::black_with_opts(16, 8, &BlackOptions { bands: 3 }).unwrap();
+        let path = run(image, &url_parameters, &OutputFormat::Heic, 0).await.unwrap();
+        let image = VipsImage::new_from_file(&path.to_string_lossy()).unwrap();
+
+        assert_eq!((image.get_width(), image.get_height()), (16, 8));
+    }
+
+    #[actix_web::test]
+    async fn test_depth_16() {
+        assert!(matches!(get_output_format(&OutputFormat::Png).await, BandFormat::Ushort));
+        assert!(matches!(get_output_format(&OutputFormat::Tiff).await, BandFormat::Ushort));
+        assert!(matches!(get_output_format(&OutputFormat::Jpg).await, BandFormat::Uchar));
+    }
+
+    #[test]
+    fn test_jxl_options() {
+        assert_eq!(jxl_options(75, 4, false, ForeignKeep::None), "[Q=75,effort=4,lossless=false,keep=none]");
+        assert_eq!(jxl_options(100, 9, true, ForeignKeep::Icc), "[Q=100,effort=9,lossless=true,keep=icc]");
+        assert_eq!(jxl_options(50, 1, false, ForeignKeep::All), "[Q=50,effort=1,lossless=false,keep=all]");
+    }
+}
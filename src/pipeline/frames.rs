@@ -0,0 +1,45 @@
+use libvips::{ops, VipsImage};
+use libvips::ops::ArrayjoinOptions;
+
+use crate::pipeline::{PipelineError, PipelineResult};
+use crate::services::vips::get_error_message;
+
+// Split animated image (vertical strip of pages) into separate frames
+pub(crate) fn split(image: VipsImage) -> PipelineResult<Vec<VipsImage>> {
+
+    let (width, height) = (image.get_width(), image.get_height());
+    let page_height = image.get_page_height();
+
+    if page_height <= 0 || page_height >= height || height % page_height != 0 {
+        return Ok(vec![image]);
+    }
+
+    let mut frames = Vec::with_capacity((height / page_height) as usize);
+
+    for page in 0..height / page_height {
+        match ops::extract_area(&image, 0, page * page_height, width, page_height) {
+            Ok(frame) => frames.push(frame),
+            Err(_) => return Err(PipelineError(format!("Failed to extract frame {page}: {}", get_error_message())))
+        }
+    }
+
+    Ok(frames)
+
+}
+
+// Join processed frames back into a vertical strip, frame metadata (delay, loop) is inherited from the first frame
+pub(crate) fn join(mut frames: Vec<VipsImage>) -> PipelineResult<VipsImage> {
+
+    if frames.len() == 1 {
+        return Ok(frames.remove(0));
+    }
+
+    match ops::arrayjoin_with_opts(&mut frames, &ArrayjoinOptions {
+        across: 1,
+        ..ArrayjoinOptions::default()
+    }) {
+        Ok(image) => Ok(image),
+        Err(_) => Err(PipelineError(format!("Failed to join animation frames: {}", get_error_message())))
+    }
+
+}
@@ -1,10 +1,145 @@
-use crate::pipeline::{PipelineError, PipelineResult};
-use crate::services::vips::get_error_message;
+use std::{env, fs};
+use std::path::Path;
+
 use libvips::{ops, VipsImage};
+use libvips::ops::{IccTransformOptions, Interpretation};
+use log::{debug, error};
+
+use crate::crypto::bytes_hash;
+use crate::parameters::{ColorProfile, UrlParameters};
+use crate::pipeline::{PipelineError, PipelineResult};
+use crate::services::vips::{get_blob, get_error_message};
+
+const ICC_FIELD: &str = "icc-profile-data";
+
+pub(crate) async fn transform(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
+
+    let profile = get_blob(&image, ICC_FIELD);
+
+    // Only RGB profiles are kept, CMYK and other color spaces are not supported by all output formats and browsers
+    if url_parameters.color_profile == ColorProfile::Keep {
+        if !is_cmyk(&image) && profile.as_deref().is_none_or(is_rgb_profile) {
+            return Ok(image);
+        }
+
+        debug!("Transforming image to sRGB, its color space cannot be kept");
+        return icc_transform(&image, "sRGB", url_parameters.depth);
+    }
+
+    let output_profile = get_output_profile(url_parameters)?;
+    let has_profile = profile.is_some();
 
-pub(crate) async fn transform(image: VipsImage) -> PipelineResult<VipsImage> {
-    match ops::icc_transform(&image, "sRGB") {
+    if has_profile || is_cmyk(&image) {
+        debug!("Transforming image to {output_profile} using embedded profile");
+        return icc_transform(&image, &output_profile, url_parameters.depth);
+    }
+
+    // Greyscale, 16-bit and other images without embedded profile are converted to sRGB first
+    let interpretation = match url_parameters.depth > 8 {
+        true => Interpretation::Rgb16,
+        false => Interpretation::Srgb
+    };
+
+    let image = match ops::colourspace(&image, interpretation) {
+        Ok(image) => image,
+        Err(_) => return Err(PipelineError(format!("Failed to convert image to sRGB: {}", get_error_message())))
+    };
+
+    if url_parameters.color_profile == ColorProfile::Srgb {
+        return Ok(image);
+    }
+
+    icc_transform(&image, &output_profile, url_parameters.depth)
+
+}
+
+fn icc_transform(image: &VipsImage, output_profile: &str, depth: u8) -> PipelineResult<VipsImage> {
+    match ops::icc_transform_with_opts(image, output_profile, &IccTransformOptions {
+        input_profile: get_import_profile(image).to_string(),
+        embedded: true,
+        depth: if depth > 8 { 16 } else { 8 },
+        ..IccTransformOptions::default()
+    }) {
         Ok(image) => Ok(image),
-        Err(_) => Err(PipelineError(format!("Failed to transform image to sRGB: {}", get_error_message())))
+        Err(_) => Err(PipelineError(format!("Failed to transform image to {output_profile}: {}", get_error_message())))
+    }
+}
+
+fn is_cmyk(image: &VipsImage) -> bool {
+    matches!(image.get_interpretation(), Ok(Interpretation::Cmyk))
+}
+
+// Data color space signature of the ICC profile header
+fn is_rgb_profile(profile: &[u8]) -> bool {
+    profile.get(16..20) == Some(b"RGB ")
+}
+
+// Fallback profile for images without embedded profile
+pub(crate) fn get_import_profile(image: &VipsImage) -> &'static str {
+    match is_cmyk(image) {
+        true => "cmyk",
+        false => "sRGB"
+    }
+}
+
+pub(crate) fn get_output_profile(url_parameters: &UrlParameters<'_>) -> PipelineResult<String> {
+    match &url_parameters.color_profile {
+        ColorProfile::Srgb | ColorProfile::Keep => Ok("sRGB".to_string()),
+        ColorProfile::P3 => Ok("p3".to_string()),
+        ColorProfile::Named(name) => {
+            let directory = env::var("ICC_DIR").unwrap_or("icc".to_string());
+
+            ["icc", "icm"].iter()
+                .map(|extension| format!("{directory}/{name}.{extension}"))
+                .find(|path| Path::new(path).is_file())
+                .ok_or_else(|| PipelineError(format!("ICC profile {name} not found in {directory}")))
+        }
+    }
+}
+
+// Profile the image is exported to after resizing, embedded profile is stored in cache when kept
+pub(crate) fn get_export_profile(image: &VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<String> {
+
+    if url_parameters.color_profile != ColorProfile::Keep {
+        return get_output_profile(url_parameters);
+    }
+
+    let profile = match get_blob(image, ICC_FIELD) {
+        Some(profile) if is_rgb_profile(&profile) => profile,
+        _ => return Ok("sRGB".to_string())
+    };
+
+    let env_cache = env::var("CACHE").unwrap_or("/tmp".to_string());
+    let directory = format!("{env_cache}/icc");
+    let path = format!("{directory}/{}.icc", bytes_hash(&profile));
+
+    if Path::new(&path).is_file() {
+        return Ok(path);
+    }
+
+    if let Err(e) = fs::create_dir_all(&directory).and_then(|_| fs::write(&path, &profile)) {
+        error!("Failed to store embedded ICC profile: {}", e);
+        return Err(PipelineError("Failed to store embedded ICC profile".to_string()));
+    }
+
+    Ok(path)
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_profile(space: &[u8; 4]) -> Vec<u8> {
+        [vec![0; 16], space.to_vec(), b"XYZ ".to_vec(), vec![0; 104]].concat()
+    }
+
+    #[test]
+    fn test_is_rgb_profile() {
+        assert!(is_rgb_profile(&get_profile(b"RGB ")));
+        assert!(!is_rgb_profile(&get_profile(b"CMYK")));
+        assert!(!is_rgb_profile(&get_profile(b"GRAY")));
+        assert!(!is_rgb_profile(&get_profile(b"Lab ")));
+        assert!(!is_rgb_profile(b"RGB "));
     }
-}
\ No newline at end of file
+}
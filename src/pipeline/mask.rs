@@ -0,0 +1,106 @@
+use libvips::{ops, VipsImage};
+use libvips::ops::{BandFormat, Interpretation, Size, ThumbnailOptions};
+use log::debug;
+
+use crate::parameters::{Mask, Radius, UrlParameters};
+use crate::pipeline::adjust::split_alpha;
+use crate::pipeline::{background, check, PipelineError, PipelineResult};
+use crate::services::vips::get_error_message;
+
+const CONTEXT: &str = "apply mask";
+
+// Shapes are drawn as SVG and rendered by libvips, masks are multiplied into the alpha channel
+pub(crate) async fn run(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
+
+    let (width, height) = (image.get_width(), image.get_height());
+    let mut masks = Vec::with_capacity(2);
+
+    if let Some(radius) = &url_parameters.radius {
+        debug!("Rounding corners");
+        masks.push(render(&get_rounded_rectangle(radius, width, height))?);
+    }
+
+    if let Some(mask) = &url_parameters.mask {
+        debug!("Applying mask {mask:?}");
+        masks.push(match mask {
+            Mask::Circle => render(&get_circle(width, height))?,
+            Mask::Ellipse => render(&get_ellipse(width, height))?,
+            Mask::Svg(path) => load(path, width, height)?
+        });
+    }
+
+    let high_depth = matches!(image.get_format(), Ok(BandFormat::Ushort));
+    let format = if high_depth { BandFormat::Ushort } else { BandFormat::Uchar };
+
+    let (image, alpha) = split_alpha(background::add_alpha(image)?)?;
+    let mut alpha = alpha.ok_or(PipelineError("Failed to apply mask: missing alpha channel".to_string()))?;
+
+    // Masks are 8-bit, existing transparency is kept
+    for mask in masks {
+        alpha = check(ops::linear(&check(ops::multiply(&alpha, &mask), CONTEXT)?, &mut [1.0 / 255.0], &mut [0.0]), CONTEXT)?;
+    }
+
+    let alpha = check(ops::cast(&alpha, format), CONTEXT)?;
+
+    check(ops::bandjoin(&mut [image, alpha]), CONTEXT)
+
+}
+
+fn get_rounded_rectangle(radius: &Radius, width: i32, height: i32) -> String {
+
+    let (top_left, top_right, bottom_right, bottom_left) = radius.resolve(width, height);
+
+    get_svg(width, height, &format!(
+        r#"<path d="M {top_left} 0 H {} A {top_right} {top_right} 0 0 1 {width} {top_right} V {} A {bottom_right} {bottom_right} 0 0 1 {} {height} H {bottom_left} A {bottom_left} {bottom_left} 0 0 1 0 {} V {top_left} A {top_left} {top_left} 0 0 1 {top_left} 0 Z"/>"#,
+        width - top_right,
+        height - bottom_right,
+        width - bottom_right,
+        height - bottom_left
+    ))
+
+}
+
+// Circle with diameter of the shorter side in the center of the image
+fn get_circle(width: i32, height: i32) -> String {
+    get_svg(width, height, &format!(r#"<circle cx="{}" cy="{}" r="{}"/>"#, width as f64 / 2.0, height as f64 / 2.0, width.min(height) as f64 / 2.0))
+}
+
+fn get_ellipse(width: i32, height: i32) -> String {
+    get_svg(width, height, &format!(r#"<ellipse cx="{0}" cy="{1}" rx="{0}" ry="{1}"/>"#, width as f64 / 2.0, height as f64 / 2.0))
+}
+
+fn get_svg(width: i32, height: i32, shape: &str) -> String {
+    format!(r##"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}" fill="#fff">{shape}</svg>"##)
+}
+
+fn render(svg: &str) -> PipelineResult<VipsImage> {
+    get_mask(check(VipsImage::new_from_buffer(svg.as_bytes(), ""), CONTEXT)?)
+}
+
+// SVG file is stretched to the image, transparent areas hide the image, black areas of files without transparency too
+fn load(path: &str, width: i32, height: i32) -> PipelineResult<VipsImage> {
+
+    let mask = match ops::thumbnail_with_opts(path, width, &ThumbnailOptions {
+        height,
+        size: Size::Force,
+        ..ThumbnailOptions::default()
+    }) {
+        Ok(mask) => mask,
+        Err(_) => return Err(PipelineError(format!("Failed to open mask {path}: {}", get_error_message())))
+    };
+
+    get_mask(mask)
+
+}
+
+// Alpha channel of transparent images, lightness of opaque ones
+fn get_mask(image: VipsImage) -> PipelineResult<VipsImage> {
+
+    let image = check(ops::cast(&check(ops::colourspace(&image, Interpretation::Srgb), CONTEXT)?, BandFormat::Uchar), CONTEXT)?;
+
+    match split_alpha(image)? {
+        (_, Some(alpha)) => Ok(alpha),
+        (color, None) => check(ops::colourspace(&color, Interpretation::BW), CONTEXT)
+    }
+
+}
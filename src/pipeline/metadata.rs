@@ -0,0 +1,313 @@
+use std::env;
+
+use libvips::VipsImage;
+use libvips::ops::ForeignKeep;
+use log::debug;
+
+use crate::parameters::{ColorProfile, Metadata, UrlParameters};
+use crate::pipeline::{PipelineError, PipelineResult};
+use crate::services::vips::{get_error_message, ImageHeader};
+
+const ICC_FIELD: &str = "icc-profile-data";
+const EXIF_FIELD: &str = "exif-data";
+const EXIF_PREFIX: &str = "exif-ifd";
+const GPS_PREFIX: &str = "exif-ifd3-";
+const XMP_FIELD: &str = "xmp-data";
+const IPTC_FIELD: &str = "iptc-data";
+const COPYRIGHT_FIELD: &str = "exif-ifd0-Copyright";
+const ARTIST_FIELD: &str = "exif-ifd0-Artist";
+
+// Image structure, animation and loader fields, not removed as metadata
+const TECHNICAL_FIELDS: [&str; 21] = [
+    "width", "height", "bands", "format", "coding", "interpretation", "xoffset", "yoffset", "xres", "yres", "filename",
+    "n-pages", "page-height", "delay", "loop", "gif-delay", "gif-loop", "background", "resolution-unit", "vips-loader", "vips-sequential"
+];
+
+// EXIF blob starts with the APP1 marker followed by a TIFF structure
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const GPS_POINTER_TAG: u16 = 0x8825;
+const IFD_ENTRY_SIZE: usize = 12;
+
+pub(crate) fn keep(url_parameters: &UrlParameters<'_>) -> ForeignKeep {
+
+    if !requires_filtering(url_parameters) {
+        return match keeps_icc(url_parameters) {
+            true => ForeignKeep::Icc,
+            false => ForeignKeep::None
+        };
+    }
+
+    ForeignKeep::All
+
+}
+
+// Remove metadata not selected by the client, strip GPS location and inject configured copyright
+pub(crate) async fn run(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
+
+    if !requires_filtering(url_parameters) {
+        return Ok(image);
+    }
+
+    let allow_gps = env::var("META_ALLOW_GPS").unwrap_or("false".to_string()) == "true";
+
+    let mut header = match ImageHeader::new(&image) {
+        Some(header) => header,
+        None => return Err(PipelineError(format!("Failed to read image metadata: {}", get_error_message())))
+    };
+
+    filter(&mut header, &url_parameters.metadata, keeps_icc(url_parameters), allow_gps, &get_injected_fields());
+
+    match header.apply(&image) {
+        Some(image) => Ok(image),
+        None => Err(PipelineError(format!("Failed to write image metadata: {}", get_error_message())))
+    }
+
+}
+
+// Fields not matching any kind of metadata (e.g. PNG comments) are kept only with all metadata
+fn filter(header: &mut ImageHeader, metadata: &Metadata, keep_icc: bool, allow_gps: bool, injected: &[(&str, String)]) {
+
+    for field in header.get_fields() {
+        let remove = match field.as_str() {
+            field if TECHNICAL_FIELDS.contains(&field) => false,
+            ICC_FIELD => !keep_icc,
+            EXIF_FIELD => !metadata.exif,
+            // XMP may carry location in any namespace, it is dropped unless location is allowed
+            XMP_FIELD => !metadata.other || !allow_gps,
+            IPTC_FIELD => !metadata.other,
+            COPYRIGHT_FIELD | ARTIST_FIELD => !metadata.copyright,
+            field if field.starts_with(GPS_PREFIX) => !metadata.exif || !allow_gps,
+            field if field.starts_with(EXIF_PREFIX) => !metadata.exif,
+            _ => !metadata.other
+        };
+
+        if remove {
+            debug!("Removing metadata field {field}");
+            header.remove(&field);
+        }
+    }
+
+    // GPS IFD is removed from the EXIF blob too, EXIF that cannot be parsed is dropped
+    if metadata.exif && !allow_gps {
+        if let Some(exif) = header.get_blob(EXIF_FIELD) {
+            match strip_gps(&exif) {
+                Some(exif) => header.set_blob(EXIF_FIELD, &exif),
+                None => header.remove(EXIF_FIELD)
+            }
+        }
+    }
+
+    // libvips builds EXIF from the remaining exif-ifd fields when saving
+    for (field, value) in injected {
+        if header.get_string(field).is_none() {
+            header.set_string(field, value);
+        }
+    }
+
+}
+
+// Wide-gamut and custom profiles are always embedded in the output
+fn keeps_icc(url_parameters: &UrlParameters<'_>) -> bool {
+    url_parameters.metadata.icc || url_parameters.color_profile != ColorProfile::Srgb
+}
+
+fn requires_filtering(url_parameters: &UrlParameters<'_>) -> bool {
+    let metadata = &url_parameters.metadata;
+    metadata.copyright || metadata.exif || metadata.other || !get_injected_fields().is_empty()
+}
+
+fn get_injected_fields() -> Vec<(&'static str, String)> {
+    [(COPYRIGHT_FIELD, "META_COPYRIGHT"), (ARTIST_FIELD, "META_ARTIST")].into_iter()
+        .filter_map(|(field, key)| match env::var(key) {
+            Ok(value) if !value.is_empty() => Some((field, value)),
+            _ => None
+        })
+        .collect()
+}
+
+// Remove GPS pointer from IFD0 and erase the GPS IFD with its values, None when EXIF cannot be parsed
+fn strip_gps(exif: &[u8]) -> Option<Vec<u8>> {
+
+    let mut data = exif.to_vec();
+    let start = if data.starts_with(EXIF_HEADER) { EXIF_HEADER.len() } else { 0 };
+    let tiff = &mut data[start..];
+
+    let big_endian = match tiff.get(0..4)? {
+        b"II*\0" => false,
+        b"MM\0*" => true,
+        _ => return None
+    };
+
+    let ifd = read_u32(tiff, 4, big_endian)? as usize;
+    let count = read_u16(tiff, ifd, big_endian)? as usize;
+    let entries = ifd + 2;
+    let end = entries + count * IFD_ENTRY_SIZE + 4;
+
+    if tiff.len() < end {
+        return None;
+    }
+
+    let index = match (0..count).find(|index| read_u16(tiff, entries + index * IFD_ENTRY_SIZE, big_endian) == Some(GPS_POINTER_TAG)) {
+        Some(index) => index,
+        None => return Some(data)
+    };
+
+    let gps = read_u32(tiff, entries + index * IFD_ENTRY_SIZE + 8, big_endian)? as usize;
+    erase_ifd(tiff, gps, big_endian);
+
+    // Following entries and the next IFD offset move up by one entry
+    let entry = entries + index * IFD_ENTRY_SIZE;
+    tiff.copy_within(entry + IFD_ENTRY_SIZE..end, entry);
+    tiff[end - IFD_ENTRY_SIZE..end].fill(0);
+    write_u16(tiff, ifd, (count - 1) as u16, big_endian);
+
+    Some(data)
+
+}
+
+// Zero the IFD and values stored outside of it, parts out of bounds are skipped
+fn erase_ifd(tiff: &mut [u8], ifd: usize, big_endian: bool) {
+
+    let count = match read_u16(tiff, ifd, big_endian) {
+        Some(count) => count as usize,
+        None => return
+    };
+
+    for index in 0..count {
+        let entry = ifd + 2 + index * IFD_ENTRY_SIZE;
+
+        let (kind, length, offset) = match (read_u16(tiff, entry + 2, big_endian), read_u32(tiff, entry + 4, big_endian), read_u32(tiff, entry + 8, big_endian)) {
+            (Some(kind), Some(length), Some(offset)) => (kind, length as usize, offset as usize),
+            _ => break
+        };
+
+        let size = get_type_size(kind).saturating_mul(length);
+
+        if size > 4 {
+            if let Some(value) = tiff.get_mut(offset..offset.saturating_add(size)) {
+                value.fill(0);
+            }
+        }
+    }
+
+    let end = (ifd + 2 + count * IFD_ENTRY_SIZE + 4).min(tiff.len());
+    tiff[ifd..end].fill(0);
+
+}
+
+fn get_type_size(kind: u16) -> usize {
+    match kind {
+        3 | 8 => 2,
+        4 | 9 | 11 => 4,
+        5 | 10 | 12 => 8,
+        _ => 1
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize, big_endian: bool) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?.try_into().ok()?;
+    Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+}
+
+fn read_u32(data: &[u8], offset: usize, big_endian: bool) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?.try_into().ok()?;
+    Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+}
+
+fn write_u16(data: &mut [u8], offset: usize, value: u16, big_endian: bool) {
+    let bytes = if big_endian { value.to_be_bytes() } else { value.to_le_bytes() };
+    data[offset..offset + 2].copy_from_slice(&bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use libvips::ops::BandFormat;
+
+    use crate::pipeline::init_vips;
+
+    use super::*;
+
+    const LATITUDE: [u8; 24] = [0x31; 24];
+
+    // IFD0 with Make and GPS pointer, GPS IFD with latitude stored outside of the IFD
+    fn get_exif(big_endian: bool) -> Vec<u8> {
+
+        let u16 = |value: u16| if big_endian { value.to_be_bytes().to_vec() } else { value.to_le_bytes().to_vec() };
+        let u32 = |value: u32| if big_endian { value.to_be_bytes().to_vec() } else { value.to_le_bytes().to_vec() };
+
+        let mut tiff = if big_endian { b"MM\0*".to_vec() } else { b"II*\0".to_vec() };
+        tiff.extend(u32(8));
+
+        // IFD0 at 8: 2 entries, ends at 8 + 2 + 24 + 4 = 38
+        tiff.extend(u16(2));
+        tiff.extend([u16(0x010F), u16(2), u32(4), b"Cam\0".to_vec()].concat());
+        tiff.extend([u16(GPS_POINTER_TAG), u16(4), u32(1), u32(38)].concat());
+        tiff.extend(u32(0));
+
+        // GPS IFD at 38: 1 entry, ends at 38 + 2 + 12 + 4 = 56
+        tiff.extend(u16(1));
+        tiff.extend([u16(0x0002), u16(5), u32(3), u32(56)].concat());
+        tiff.extend(u32(0));
+
+        tiff.extend(LATITUDE);
+
+        [EXIF_HEADER.to_vec(), tiff].concat()
+
+    }
+
+    #[test]
+    fn test_strip_gps() {
+        for big_endian in [false, true] {
+            let exif = strip_gps(&get_exif(big_endian)).unwrap();
+            let tiff = &exif[EXIF_HEADER.len()..];
+
+            assert_eq!(exif.len(), get_exif(big_endian).len());
+            assert_eq!(read_u16(tiff, 8, big_endian), Some(1));
+            assert_eq!(read_u16(tiff, 10, big_endian), Some(0x010F));
+            assert_eq!(&tiff[18..22], b"Cam\0");
+            assert_eq!(read_u32(tiff, 22, big_endian), Some(0));
+            assert!(tiff[22..].iter().all(|byte| *byte == 0));
+            assert!(!tiff.windows(LATITUDE.len()).any(|window| window == LATITUDE));
+        }
+    }
+
+    #[test]
+    fn test_strip_gps_unchanged() {
+        let mut exif = get_exif(false);
+        exif[EXIF_HEADER.len() + 8] = 1;
+        exif.truncate(EXIF_HEADER.len() + 10 + IFD_ENTRY_SIZE);
+        exif.extend([0; 4]);
+
+        assert_eq!(strip_gps(&exif), Some(exif.clone()));
+        assert_eq!(strip_gps(&exif[EXIF_HEADER.len()..]), Some(exif[EXIF_HEADER.len()..].to_vec()));
+    }
+
+    #[test]
+    fn test_strip_gps_invalid() {
+        assert_eq!(strip_gps(b""), None);
+        assert_eq!(strip_gps(b"Exif\0\0XX*\0\x08\0\0\0"), None);
+        assert_eq!(strip_gps(&get_exif(false)[..20]), None);
+    }
+
+    #[test]
+    fn test_filter_none_with_injected() {
+        init_vips();
+
+        let image = VipsImage::new_from_memory(&[0, 0, 0], 1, 1, 3, BandFormat::Uchar).unwrap();
+        let mut header = ImageHeader::new(&image).unwrap();
+
+        header.set_string("png-comment-0-Comment", "Private comment");
+        header.set_string("exif-ifd0-Make", "Cam");
+        header.set_blob(EXIF_FIELD, &get_exif(false));
+        header.set_blob(XMP_FIELD, b"<x:xmpmeta/>");
+        header.set_blob(IPTC_FIELD, b"\x1c\x02\x00");
+
+        filter(&mut header, &Metadata::default(), false, false, &[(COPYRIGHT_FIELD, "ACME".to_string())]);
+
+        let fields: Vec<String> = header.get_fields().into_iter().filter(|field| !TECHNICAL_FIELDS.contains(&field.as_str())).collect();
+
+        assert_eq!(fields, vec![COPYRIGHT_FIELD.to_string()]);
+        assert_eq!(header.get_string(COPYRIGHT_FIELD), Some("ACME".to_string()));
+    }
+
+}
@@ -1,9 +1,11 @@
 use std::path::PathBuf;
+use libvips::VipsImage;
 use log::debug;
 
 use crate::cache;
-use crate::parameters::{Rotate, UrlParameters};
-use crate::services::formats::{is_svg, OutputFormat, supports_transparency, validate_output_format};
+use crate::parameters::{Flip, Rotate, UrlParameters};
+use crate::services::vips::get_error_message;
+use crate::services::formats::{has_mask, is_svg, OutputFormat, supports_alpha, supports_animation, validate_output_format};
 
 mod thumbnail;
 mod rotate;
@@ -13,12 +15,32 @@ mod finalize;
 mod rasterize;
 mod background;
 mod icc;
+mod frames;
+mod quality;
+mod metadata;
+mod canvas;
+mod trim;
+mod effects;
+mod adjust;
+mod filter;
+mod redact;
+mod overlay;
+mod text;
+mod mask;
 
 pub type PipelineResult<T> = Result<T, PipelineError>;
 
 #[derive(Debug)]
 pub struct PipelineError(pub String);
 
+// Convert libvips result to pipeline error with the libvips error message, e.g. check(ops::copy(&image), "copy image")
+pub(crate) fn check<T, E>(result: Result<T, E>, context: &str) -> PipelineResult<T> {
+    match result {
+        Ok(value) => Ok(value),
+        Err(_) => Err(PipelineError(format!("Failed to {context}: {}", get_error_message())))
+    }
+}
+
 pub enum PipelineOutput {
     Image(PathBuf),
     OutputFormat(OutputFormat)
@@ -28,6 +50,14 @@ pub async fn run(url_parameters: &UrlParameters<'_>, output_format: OutputFormat
     let mut image = thumbnail::run(url_parameters.path, url_parameters).await?;
 
     if output_format == OutputFormat::Pdf {
+        if url_parameters.mandatory_watermark.is_some() {
+            return Err(PipelineError("Documents cannot be served without watermark".to_string()));
+        }
+
+        if url_parameters.redact.is_some() {
+            return Err(PipelineError("Documents cannot be served without redaction".to_string()));
+        }
+
         return Ok(PipelineOutput::Image(cache::get_document_path_from_url_parameters(url_parameters).into()));
     }
     
@@ -35,19 +65,58 @@ pub async fn run(url_parameters: &UrlParameters<'_>, output_format: OutputFormat
         image = rasterize::run(image, url_parameters).await?;
     }
 
-    debug!("Performing autorotate");
-    image = rotate::autorotate(image).await?;
+    let mut frames = frames::split(image)?;
+
+    if !supports_animation(&output_format) {
+        frames.truncate(1);
+    }
+
+    debug!("Performing autorotate on {} frame(s)", frames.len());
+    let mut rotated_frames = Vec::with_capacity(frames.len());
+
+    for frame in frames {
+        rotated_frames.push(rotate::autorotate(frame, url_parameters).await?);
+    }
+
+    if url_parameters.redact.is_some() {
+        debug!("Redacting regions");
+        rotated_frames = redact::run(rotated_frames, url_parameters).await?;
+    }
+
+    if url_parameters.trim.is_some() {
+        debug!("Trimming borders");
+        rotated_frames = trim::run(rotated_frames, url_parameters).await?;
+    }
 
-    let valid_output_format = validate_output_format(&image, url_parameters, &output_format)?;
+    let valid_output_format = validate_output_format(&rotated_frames[0], url_parameters, &output_format)?;
 
     if valid_output_format != output_format {
         return Ok(PipelineOutput::OutputFormat(valid_output_format));
     }
 
     let output_format = valid_output_format;
+    let mut processed_frames = Vec::with_capacity(rotated_frames.len());
+
+    for frame in rotated_frames {
+        processed_frames.push(process_frame(frame, url_parameters, &output_format).await?);
+    }
+
+    let page_height = processed_frames[0].get_height();
+    let image = frames::join(processed_frames)?;
+
+    debug!("Filtering metadata");
+    let image = metadata::run(image, url_parameters).await?;
+
+    match finalize::run(image, url_parameters, &output_format, page_height).await {
+        Ok(result) => Ok(PipelineOutput::Image(result)),
+        Err(e) => Err(e)
+    }
+}
+
+async fn process_frame(mut image: VipsImage, url_parameters: &UrlParameters<'_>, output_format: &OutputFormat) -> PipelineResult<VipsImage> {
 
     debug!("Performing ICC transform");
-    image = icc::transform(image).await?;
+    image = icc::transform(image, url_parameters).await?;
 
     // if url_parameters.crop.is_some() {
     //     crop::run(&image, &url_parameters, &output_format).await?;
@@ -57,18 +126,74 @@ pub async fn run(url_parameters: &UrlParameters<'_>, output_format: OutputFormat
         image = resize::run(image, url_parameters).await?;
     }
 
+    if url_parameters.blur.is_some() || url_parameters.sharpen.is_some() || url_parameters.pixelate.is_some() {
+        debug!("Applying effects");
+        image = effects::run(image, url_parameters).await?;
+    }
+
+    if !url_parameters.adjustments.is_none() {
+        debug!("Adjusting tone and colors");
+        image = adjust::run(image, url_parameters).await?;
+    }
+
+    if url_parameters.filter.is_some() {
+        debug!("Applying filter");
+        image = filter::run(image, url_parameters).await?;
+    }
+
     if url_parameters.rotate != Rotate::No {
         debug!("Rotating image");
         image = rotate::run(image, url_parameters).await?;
     }
 
-    if supports_transparency(url_parameters.path) && output_format != OutputFormat::Jpg {
+    if url_parameters.flip != Flip::No {
+        debug!("Flipping image");
+        image = rotate::flip(image, url_parameters).await?;
+    }
+
+    if has_mask(url_parameters) {
+        debug!("Applying shape mask");
+        image = mask::run(image, url_parameters).await?;
+    }
+
+    // Extended canvas is filled with the background as a whole, so that gradients continue below the image
+    if url_parameters.padding.is_some() || url_parameters.canvas.is_some() {
+        debug!("Extending image canvas");
+        image = canvas::run(image, url_parameters).await?;
+    } else if image.image_hasalpha() && url_parameters.background.is_some() {
         debug!("Applying background");
         image = background::run(image, url_parameters).await?;
     }
 
-    match finalize::run(image, url_parameters, &output_format).await {
-        Ok(result) => Ok(PipelineOutput::Image(result)),
-        Err(e) => Err(e)
+    if url_parameters.text.is_some() {
+        debug!("Rendering text");
+        image = text::run(image, url_parameters).await?;
     }
-}
\ No newline at end of file
+
+    if url_parameters.watermark.is_some() || url_parameters.mandatory_watermark.is_some() {
+        debug!("Applying watermark");
+        image = overlay::run(image, url_parameters).await?;
+    }
+
+    if image.image_hasalpha() && !supports_alpha(output_format) {
+        debug!("Flattening transparent image");
+        image = background::flatten(image, url_parameters).await?;
+    }
+
+    Ok(image)
+
+}
+
+// libvips is initialized once for all tests and never shut down, tests run in parallel
+#[cfg(test)]
+pub(crate) fn init_vips() {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| std::mem::forget(libvips::VipsApp::new("picturium-test", false).unwrap()));
+}
+
+// URL parameters of a test fixture as parsed from the query string, e.g. get_url_parameters("data/image.jpg", "w=100&rot=90")
+#[cfg(test)]
+pub(crate) fn get_url_parameters(path: &'static str, query: &str) -> UrlParameters<'static> {
+    let raw_url_parameters = actix_web::web::Query::<crate::parameters::RawUrlParameters>::from_query(query).unwrap().into_inner();
+    UrlParameters::new(path, raw_url_parameters)
+}
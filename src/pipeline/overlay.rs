@@ -0,0 +1,144 @@
+use libvips::{ops, VipsImage};
+use libvips::ops::{BandFormat, BlendMode, Composite2Options, EmbedOptions, ExtractBandOptions, Interpretation, Size, ThumbnailImageOptions};
+use log::debug;
+
+use crate::parameters::{Origin, UrlParameters, Watermark};
+use crate::pipeline::{background, check, PipelineError, PipelineResult};
+use crate::services::vips::get_error_message;
+use crate::services::watermarks;
+
+const CONTEXT: &str = "apply watermark";
+
+// Mandatory watermark is applied first, requested watermark on top of it
+pub(crate) async fn run(mut image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
+
+    for watermark in [&url_parameters.mandatory_watermark, &url_parameters.watermark].into_iter().flatten() {
+        debug!("Applying watermark {}", watermark.image);
+        image = apply(image, watermark)?;
+    }
+
+    Ok(image)
+
+}
+
+fn apply(image: VipsImage, watermark: &Watermark) -> PipelineResult<VipsImage> {
+
+    let high_depth = matches!(image.get_format(), Ok(BandFormat::Ushort));
+    let (width, height) = (image.get_width(), image.get_height());
+
+    let overlay = load(watermark, width, high_depth)?;
+
+    let (overlay, (x, y)) = match watermark.tile {
+        true => (tile(&overlay, watermark, width, height)?, (0, 0)),
+        false => {
+            let position = get_position(watermark, (width, height), (overlay.get_width(), overlay.get_height()));
+            (overlay, position)
+        }
+    };
+
+    composite(image, &overlay, x, y)
+
+}
+
+// Place overlay with alpha channel over the image, overlay must be in the depth of the image
+pub(crate) fn composite(image: VipsImage, overlay: &VipsImage, x: i32, y: i32) -> PipelineResult<VipsImage> {
+
+    let high_depth = matches!(image.get_format(), Ok(BandFormat::Ushort));
+    let opaque = !image.image_hasalpha();
+
+    let image = check(ops::composite_2_with_opts(&image, overlay, BlendMode::Over, &Composite2Options {
+        x,
+        y,
+        compositing_space: if high_depth { Interpretation::Rgb16 } else { Interpretation::Srgb },
+        ..Composite2Options::default()
+    }), CONTEXT)?;
+
+    // Overlay must not make an opaque image transparent
+    match opaque {
+        true => check(ops::extract_band_with_opts(&image, 0, &ExtractBandOptions { n: image.get_bands() - 1 }), CONTEXT),
+        false => Ok(image)
+    }
+
+}
+
+// Convert 8-bit overlay to 16-bit and vice versa
+pub(crate) fn to_depth(overlay: VipsImage, high_depth: bool) -> PipelineResult<VipsImage> {
+    match (high_depth, matches!(overlay.get_format(), Ok(BandFormat::Ushort))) {
+        (true, false) => check(ops::cast(&check(ops::linear(&overlay, &mut [257.0], &mut [0.0]), CONTEXT)?, BandFormat::Ushort), CONTEXT),
+        (false, true) => check(ops::cast(&check(ops::linear(&overlay, &mut [1.0 / 257.0], &mut [0.0]), CONTEXT)?, BandFormat::Uchar), CONTEXT),
+        _ => Ok(overlay)
+    }
+}
+
+// Load watermark with alpha channel in the depth of the image, scaled relative to the image width
+fn load(watermark: &Watermark, width: i32, high_depth: bool) -> PipelineResult<VipsImage> {
+
+    let path = watermarks::get_path(watermark);
+
+    let overlay = match VipsImage::new_from_file(&path.to_string_lossy()) {
+        Ok(overlay) => overlay,
+        Err(_) => return Err(PipelineError(format!("Failed to open watermark {path:?}: {}", get_error_message())))
+    };
+
+    // Alpha is premultiplied by thumbnail, height is unconstrained
+    let overlay = match watermark.scale {
+        Some(scale) => check(ops::thumbnail_image_with_opts(&overlay, ((width as f64 * scale).round() as i32).max(1), &ThumbnailImageOptions {
+            height: 10_000_000,
+            size: Size::Both,
+            ..ThumbnailImageOptions::default()
+        }), CONTEXT)?,
+        None => overlay
+    };
+
+    let overlay = background::add_alpha(to_depth(overlay, high_depth)?)?;
+
+    if watermark.opacity >= 1.0 {
+        return Ok(overlay);
+    }
+
+    let format = overlay.get_format().unwrap_or(BandFormat::Uchar);
+    let overlay = check(ops::linear(&overlay, &mut [1.0, 1.0, 1.0, watermark.opacity], &mut [0.0]), CONTEXT)?;
+
+    check(ops::cast(&overlay, format), CONTEXT)
+
+}
+
+// Repeat watermark over the whole image, offsets are used as spacing between tiles
+fn tile(overlay: &VipsImage, watermark: &Watermark, width: i32, height: i32) -> PipelineResult<VipsImage> {
+
+    let (spacing_x, spacing_y) = (watermark.offset.0.max(0), watermark.offset.1.max(0));
+    let (tile_width, tile_height) = (overlay.get_width() + spacing_x, overlay.get_height() + spacing_y);
+
+    let cell = check(ops::embed_with_opts(overlay, 0, 0, tile_width, tile_height, &EmbedOptions {
+        background: vec![0.0; overlay.get_bands() as usize],
+        ..EmbedOptions::default()
+    }), CONTEXT)?;
+
+    let across = (width + tile_width - 1) / tile_width;
+    let down = (height + tile_height - 1) / tile_height;
+
+    let tiles = check(ops::replicate(&cell, across, down), CONTEXT)?;
+
+    check(ops::extract_area(&tiles, 0, 0, width, height), CONTEXT)
+
+}
+
+// Offsets are margins from the edges of the origin, on centered axes they shift right and down
+fn get_position(watermark: &Watermark, outer: (i32, i32), inner: (i32, i32)) -> (i32, i32) {
+
+    let (x, y) = watermark.origin.position(outer, inner);
+    let (offset_x, offset_y) = watermark.offset;
+
+    let x = match watermark.origin {
+        Origin::TopRight | Origin::RightCenter | Origin::BottomRight => x - offset_x,
+        _ => x + offset_x
+    };
+
+    let y = match watermark.origin {
+        Origin::BottomLeft | Origin::BottomCenter | Origin::BottomRight => y - offset_y,
+        _ => y + offset_y
+    };
+
+    (x, y)
+
+}
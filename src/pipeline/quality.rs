@@ -0,0 +1,147 @@
+use std::fs;
+
+use libvips::{ops, VipsImage};
+use libvips::ops::{BandFormat, Interpretation};
+use log::{debug, error};
+
+use crate::cache;
+use crate::parameters::{Target, UrlParameters};
+use crate::pipeline::{check, PipelineError, PipelineResult};
+use crate::services::vips::get_error_message;
+
+const CONTEXT: &str = "measure image quality";
+
+const MIN_QUALITY: i32 = 10;
+const MAX_QUALITY: i32 = 100;
+
+// Area of the reference image used for measuring, larger outputs are measured on a full-resolution crop
+const REFERENCE_PIXELS: f64 = 4_194_304.0; // px, 2048x2048
+
+// SSIM stabilization constants for 8-bit images
+const SSIM_C1: f64 = 6.5025; // (0.01 * 255)^2
+const SSIM_C2: f64 = 58.5225; // (0.03 * 255)^2
+
+// Find the lowest encoder quality reaching the target structural similarity, chosen quality is cached per variant
+pub(crate) fn search<F>(image: &VipsImage, url_parameters: &UrlParameters<'_>, cache_path: &str, page_height: i32, target: Target, encode: F) -> PipelineResult<i32>
+    where F: Fn(&VipsImage, i32) -> Result<Vec<u8>, libvips::error::Error> {
+
+    let quality_path = cache::get_quality_path(cache_path);
+
+    if cache::is_cached(&quality_path, url_parameters) {
+        if let Some(quality) = fs::read_to_string(&quality_path).ok().and_then(|quality| quality.trim().parse::<i32>().ok()) {
+            debug!("Using cached target quality: {quality}%");
+            return Ok(quality);
+        }
+    }
+
+    let reference = get_reference(image, page_height)?;
+    let (mut low, mut high) = (MIN_QUALITY, MAX_QUALITY);
+    let mut quality = MAX_QUALITY;
+
+    while low <= high {
+        let candidate = (low + high) / 2;
+
+        let buffer = match encode(&reference, candidate) {
+            Ok(buffer) => buffer,
+            Err(_) => return Err(PipelineError(format!("Failed to encode image for quality search: {}", get_error_message())))
+        };
+
+        let encoded = match VipsImage::new_from_buffer(&buffer, "") {
+            Ok(encoded) => encoded,
+            Err(_) => return Err(PipelineError(format!("Failed to decode image for quality search: {}", get_error_message())))
+        };
+
+        let score = ssim(&reference, &encoded)?;
+        debug!("Quality search: {candidate}% scored SSIM {score:.4}");
+
+        if score >= target.ssim() {
+            quality = candidate;
+            high = candidate - 1;
+        } else {
+            low = candidate + 1;
+        }
+    }
+
+    debug!("Serving image with target quality: {quality}%");
+
+    if let Err(e) = fs::write(&quality_path, quality.to_string()) {
+        error!("Failed to write target quality cache file: {}", e);
+    }
+
+    Ok(quality)
+
+}
+
+// First frame of the image at output size, centered crop of it for large images so that artefacts are measured at the served scale
+fn get_reference(image: &VipsImage, page_height: i32) -> PipelineResult<VipsImage> {
+
+    let (width, height) = (image.get_width(), page_height.min(image.get_height()));
+    let scale = (REFERENCE_PIXELS / (width as f64 * height as f64)).sqrt().min(1.0);
+
+    let (crop_width, crop_height) = ((width as f64 * scale).round().max(1.0) as i32, (height as f64 * scale).round().max(1.0) as i32);
+
+    match ops::extract_area(image, (width - crop_width) / 2, (height - crop_height) / 2, crop_width, crop_height) {
+        Ok(reference) => Ok(reference),
+        Err(_) => Err(PipelineError(format!("Failed to extract reference image: {}", get_error_message())))
+    }
+
+}
+
+// Mean structural similarity of luminance computed with gaussian windows
+fn ssim(original: &VipsImage, encoded: &VipsImage) -> PipelineResult<f64> {
+
+    let x = luminance(original)?;
+    let y = luminance(encoded)?;
+
+    let mu_x = check(ops::gaussblur(&x, 1.5), CONTEXT)?;
+    let mu_y = check(ops::gaussblur(&y, 1.5), CONTEXT)?;
+    let mu_x_mu_y = check(ops::multiply(&mu_x, &mu_y), CONTEXT)?;
+    let mu_x_sq = check(ops::multiply(&mu_x, &mu_x), CONTEXT)?;
+    let mu_y_sq = check(ops::multiply(&mu_y, &mu_y), CONTEXT)?;
+
+    let sigma_x_sq = check(ops::subtract(&check(ops::gaussblur(&check(ops::multiply(&x, &x), CONTEXT)?, 1.5), CONTEXT)?, &mu_x_sq), CONTEXT)?;
+    let sigma_y_sq = check(ops::subtract(&check(ops::gaussblur(&check(ops::multiply(&y, &y), CONTEXT)?, 1.5), CONTEXT)?, &mu_y_sq), CONTEXT)?;
+    let sigma_xy = check(ops::subtract(&check(ops::gaussblur(&check(ops::multiply(&x, &y), CONTEXT)?, 1.5), CONTEXT)?, &mu_x_mu_y), CONTEXT)?;
+
+    let numerator = check(ops::multiply(
+        &check(ops::linear(&mu_x_mu_y, &mut [2.0], &mut [SSIM_C1]), CONTEXT)?,
+        &check(ops::linear(&sigma_xy, &mut [2.0], &mut [SSIM_C2]), CONTEXT)?
+    ), CONTEXT)?;
+
+    let denominator = check(ops::multiply(
+        &check(ops::linear(&check(ops::add(&mu_x_sq, &mu_y_sq), CONTEXT)?, &mut [1.0], &mut [SSIM_C1]), CONTEXT)?,
+        &check(ops::linear(&check(ops::add(&sigma_x_sq, &sigma_y_sq), CONTEXT)?, &mut [1.0], &mut [SSIM_C2]), CONTEXT)?
+    ), CONTEXT)?;
+
+    check(ops::avg(&check(ops::divide(&numerator, &denominator), CONTEXT)?), CONTEXT)
+
+}
+
+fn luminance(image: &VipsImage) -> PipelineResult<VipsImage> {
+    let grey = check(ops::colourspace(image, Interpretation::BW), CONTEXT)?;
+    let grey = check(ops::extract_band(&grey, 0), CONTEXT)?;
+    check(ops::cast(&grey, BandFormat::Float), CONTEXT)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::init_vips;
+
+    use super::*;
+
+    fn get_reference_dimensions(width: i32, height: i32, page_height: i32) -> (i32, i32) {
+        init_vips();
+
+        let reference = get_reference(&ops::black(width, height).unwrap(), page_height).unwrap();
+        (reference.get_width(), reference.get_height())
+    }
+
+    #[test]
+    fn test_get_reference() {
+        assert_eq!(get_reference_dimensions(1000, 500, 500), (1000, 500));
+        assert_eq!(get_reference_dimensions(2048, 2048, 2048), (2048, 2048));
+        assert_eq!(get_reference_dimensions(4096, 4096, 4096), (2048, 2048));
+        assert_eq!(get_reference_dimensions(8000, 2000, 2000), (4096, 1024));
+        assert_eq!(get_reference_dimensions(1000, 400, 100), (1000, 100));
+    }
+}
@@ -10,7 +10,12 @@ use crate::services::vips::get_error_message;
 // Rasterize SVG image to bitmap
 pub(crate) async fn run(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
 
-    let (width, height) = get_rasterize_dimensions(&image, url_parameters);
+    // Redacted regions refer to the image at its original size, the image is resized later
+    let (width, height) = match url_parameters.redact.is_some() {
+        true => (image.get_width(), image.get_height()),
+        false => get_rasterize_dimensions(&image, url_parameters)
+    };
+
     debug!("Rasterizing SVG image to {}x{}", width, height);
 
     match ops::thumbnail_with_opts(&url_parameters.path.to_string_lossy(), width, &ThumbnailOptions {
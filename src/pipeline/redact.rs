@@ -0,0 +1,49 @@
+use libvips::{ops, VipsImage};
+use log::debug;
+
+use crate::parameters::{Blur, Pixelate, RedactMode, UrlParameters};
+use crate::pipeline::{check, effects, PipelineResult};
+
+const CONTEXT: &str = "redact image";
+
+// Strength relative to the longer side of the region, so that redacted content cannot be recognized
+const BLUR_DIVISOR: f64 = 8.0;
+const MAX_BLUR_SIGMA: f64 = 100.0;
+const PIXELATE_BLOCKS: i32 = 8;
+
+// Regions refer to the original image, so redaction runs before trim and resize
+pub(crate) async fn run(frames: Vec<VipsImage>, url_parameters: &UrlParameters<'_>) -> PipelineResult<Vec<VipsImage>> {
+
+    let redact = match &url_parameters.redact {
+        Some(redact) => redact,
+        None => return Ok(frames)
+    };
+
+    let mut redacted_frames = Vec::with_capacity(frames.len());
+
+    for mut frame in frames {
+        for region in &redact.regions {
+            let (x, y, width, height) = region.resolve(frame.get_width(), frame.get_height());
+
+            if width <= 0 || height <= 0 {
+                continue;
+            }
+
+            debug!("Redacting {width}x{height} region at {x},{y}");
+
+            let area = check(ops::extract_area(&frame, x, y, width, height), CONTEXT)?;
+
+            let area = match redact.mode {
+                RedactMode::Blur => effects::blur(&area, &Blur { sigma: (width.max(height) as f64 / BLUR_DIVISOR).clamp(1.0, MAX_BLUR_SIGMA) })?,
+                RedactMode::Pixelate => effects::pixelate(&area, &Pixelate { size: (width.max(height) / PIXELATE_BLOCKS).clamp(2, u16::MAX as i32) as u16 })?
+            };
+
+            frame = check(ops::insert(&frame, &area, x, y), CONTEXT)?;
+        }
+
+        redacted_frames.push(frame);
+    }
+
+    Ok(redacted_frames)
+
+}
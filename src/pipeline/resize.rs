@@ -1,10 +1,10 @@
 use std::mem::swap;
 use libvips::{ops, VipsImage};
-use libvips::ops::{Interesting, Size, ThumbnailImageOptions};
+use libvips::ops::{BandFormat, Interesting, Size, ThumbnailImageOptions};
 use log::{debug, error};
 
 use crate::parameters::{Rotate, UrlParameters};
-use crate::pipeline::{PipelineError, PipelineResult};
+use crate::pipeline::{icc, PipelineError, PipelineResult};
 use crate::services::vips::get_error_message;
 
 pub(crate) async fn run(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
@@ -12,22 +12,38 @@ pub(crate) async fn run(image: VipsImage, url_parameters: &UrlParameters<'_>) ->
     let (width, height) = get_pipeline_dimensions(&image, url_parameters);
     debug!("Resizing image to {}x{}", width, height);
 
+    // 16-bit images are already in the output profile after icc::transform, export by thumbnail would reduce them to 8 bits
+    let high_depth = matches!(image.get_format(), Ok(BandFormat::Ushort));
+
+    let (import_profile, export_profile) = match high_depth {
+        true => (String::new(), String::new()),
+        false => (icc::get_import_profile(&image).to_string(), icc::get_export_profile(&image, url_parameters)?)
+    };
+
+    // libvips thumbnail premultiplies images with alpha channel before shrinking and unpremultiplies them after, avoiding dark fringes
     let image = ops::thumbnail_image_with_opts(&image, width, &ThumbnailImageOptions {
         height,
         size: Size::Down,
-        crop: Interesting::Centre,
-        import_profile: "sRGB".into(),
-        export_profile: "sRGB".into(),
+        // Letterboxed images fit within dimensions, canvas fills the rest
+        crop: if url_parameters.canvas.is_some() { Interesting::None } else { Interesting::Centre },
+        import_profile,
+        export_profile,
         ..ThumbnailImageOptions::default()
     });
 
-    Ok(match image {
+    let image = match image {
         Ok(image) => image,
         Err(_) => {
             error!("Failed to resize image {} with dimensions {width}x{height}: {}", url_parameters.path.to_string_lossy(), get_error_message());
             return Err(PipelineError("Failed to resize image".to_string()))
         }
-    })
+    };
+
+    if high_depth && !matches!(image.get_format(), Ok(BandFormat::Ushort)) {
+        return Err(PipelineError("Failed to resize image: 16-bit depth was not kept".to_string()));
+    }
+
+    Ok(image)
 
 }
 
@@ -35,43 +51,29 @@ pub(crate) fn get_original_dimensions(image: &VipsImage) -> (i32, i32) {
     (image.get_width(), image.get_height())
 }
 
-pub(crate) fn get_dimensions(image: &VipsImage, url_parameters: &UrlParameters<'_>) -> (i32, i32) {
-
-    let (mut width, mut height) = (url_parameters.width, url_parameters.height);
-
-    if width.is_none() && height.is_none() {
-        width = Some(image.get_width() as u16);
-    }
+// Dimensions of the image before rasterization after applying orientation override, which swaps sides for orientations 5-8
+fn get_oriented_dimensions(image: &VipsImage, url_parameters: &UrlParameters<'_>) -> (i32, i32) {
 
     let (original_width, original_height) = get_original_dimensions(image);
-    let ratio = original_width as f64 / original_height as f64;
-
-    if width.is_none() {
-        width = Some((height.unwrap() as f64 * ratio).round() as u16);
-    }
 
-    if height.is_none() {
-        height = Some((width.unwrap() as f64 / ratio).round() as u16);
+    match url_parameters.orientation {
+        Some(5..=8) => (original_height, original_width),
+        _ => (original_width, original_height)
     }
 
-    (width.unwrap().into(), height.unwrap().into())
-
 }
 
-fn get_pipeline_dimensions(image: &VipsImage, url_parameters: &UrlParameters<'_>) -> (i32, i32) {
+// Dimensions of the output image, missing dimension is computed from the ratio of the rotated image
+fn get_dimensions(dimensions: (i32, i32), url_parameters: &UrlParameters<'_>) -> (i32, i32) {
 
     let (mut width, mut height) = (url_parameters.width, url_parameters.height);
-
-    if url_parameters.rotate == Rotate::Left || url_parameters.rotate == Rotate::Right {
-        swap(&mut width, &mut height);
-    }
+    let (rotated_width, rotated_height) = get_rotated_dimensions(dimensions, url_parameters.rotate);
 
     if width.is_none() && height.is_none() {
-        width = Some(image.get_width() as u16);
+        width = Some(rotated_width.round() as u16);
     }
 
-    let (original_width, original_height) = get_original_dimensions(image);
-    let ratio = original_width as f64 / original_height as f64;
+    let ratio = rotated_width / rotated_height;
 
     if width.is_none() {
         width = Some((height.unwrap() as f64 * ratio).round() as u16);
@@ -82,42 +84,144 @@ fn get_pipeline_dimensions(image: &VipsImage, url_parameters: &UrlParameters<'_>
     }
 
     (width.unwrap().into(), height.unwrap().into())
+
+}
+
+// Image is already oriented when resized in pipeline
+fn get_pipeline_dimensions(image: &VipsImage, url_parameters: &UrlParameters<'_>) -> (i32, i32) {
+
+    let dimensions = get_original_dimensions(image);
+    let (mut width, mut height) = get_dimensions(dimensions, url_parameters);
+
+    apply_rotate_dimensions(dimensions, url_parameters, (&mut width, &mut height));
+
+    (width, height)
     
 }
 
+// Image is oriented after rasterization, so the dimensions are converted back to the unoriented image
 pub(crate) fn get_rasterize_dimensions(image: &VipsImage, url_parameters: &UrlParameters<'_>) -> (i32, i32) {
 
-    let (output_width, output_height) = get_dimensions(image, url_parameters);
+    let dimensions = get_oriented_dimensions(image, url_parameters);
+    let (output_width, output_height) = get_dimensions(dimensions, url_parameters);
     let (mut return_width, mut return_height) = (output_width, output_height);
 
-    apply_rotate_dimensions(image, url_parameters, (&mut return_width, &mut return_height));
+    apply_rotate_dimensions(dimensions, url_parameters, (&mut return_width, &mut return_height));
 
-    // TODO > Check crop, if not set, return output_width, output_height
+    if matches!(url_parameters.orientation, Some(5..=8)) {
+        swap(&mut return_width, &mut return_height);
+    }
 
-    // let (original_width, original_height) = get_original_dimensions(image);
-    // let ratio = original_width as f64 / original_height as f64;
+    // TODO > Check crop, if not set, return output_width, output_height
 
     debug!("Preprocessing image to {}x{}", return_width, return_height);
     (return_width, return_height)
 
 }
 
-fn apply_rotate_dimensions(image: &VipsImage, url_parameters: &UrlParameters<'_>, (return_width, return_height): (&mut i32, &mut i32)) {
+// Bounding box of the image rotated by the requested angle
+fn get_rotated_dimensions((width, height): (i32, i32), rotate: Rotate) -> (f64, f64) {
+
+    let (width, height) = (width as f64, height as f64);
+
+    let angle = rotate.degrees().to_radians();
+    let (sin, cos) = (angle.sin().abs(), angle.cos().abs());
+
+    (width * cos + height * sin, width * sin + height * cos)
+
+}
+
+// Requested dimensions refer to the final bounding box, convert them to dimensions of the image before rotation
+fn apply_rotate_dimensions(dimensions: (i32, i32), url_parameters: &UrlParameters<'_>, (return_width, return_height): (&mut i32, &mut i32)) {
+
+    match url_parameters.rotate {
+        Rotate::No | Rotate::UpsideDown => {},
+        Rotate::Left | Rotate::Right => swap(return_width, return_height),
+        Rotate::Angle(_) => {
+            // Rotated image fits within the bounding box, exposed corners are filled with background
+            let (rotated_width, rotated_height) = get_rotated_dimensions(dimensions, url_parameters.rotate);
+            let scale = (*return_width as f64 / rotated_width).min(*return_height as f64 / rotated_height);
 
-    if url_parameters.rotate == Rotate::No || url_parameters.rotate == Rotate::UpsideDown {
-        return;
+            let (width, height) = dimensions;
+            *return_width = (width as f64 * scale).round().max(1.0) as i32;
+            *return_height = (height as f64 * scale).round().max(1.0) as i32;
+        }
     }
 
-    let (original_width, original_height) = get_original_dimensions(image);
-    let ratio = *return_width as f64 / original_height as f64;
-    
-    if return_width > return_height {
-        *return_width = *return_height + 2;
-        *return_height = (original_height as f64 * ratio).round() as i32;
-        return;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::{get_url_parameters, init_vips};
+
+    use super::*;
+
+    // Pixels are computed lazily, only dimensions are used
+    fn get_image(width: i32, height: i32) -> VipsImage {
+        init_vips();
+        ops::black(width, height).unwrap()
+    }
+
+    #[test]
+    fn test_get_oriented_dimensions() {
+        let image = get_image(300, 200);
+
+        for orientation in 1..=4 {
+            assert_eq!(get_oriented_dimensions(&image, &get_url_parameters("data/resize.jpg", &format!("orient={orientation}"))), (300, 200));
+        }
+
+        for orientation in 5..=8 {
+            assert_eq!(get_oriented_dimensions(&image, &get_url_parameters("data/resize.jpg", &format!("orient={orientation}"))), (200, 300));
+        }
+
+        assert_eq!(get_oriented_dimensions(&image, &get_url_parameters("data/resize.jpg", "")), (300, 200));
+        assert_eq!(get_oriented_dimensions(&image, &get_url_parameters("data/resize.jpg", "autorot=false")), (300, 200));
     }
 
-    *return_height = *return_width + 2;
-    *return_width = (original_width as f64 * ratio).round() as i32;
+    #[test]
+    fn test_get_pipeline_dimensions() {
+        let image = get_image(300, 200);
+
+        assert_eq!(get_pipeline_dimensions(&image, &get_url_parameters("data/resize.jpg", "")), (300, 200));
+        assert_eq!(get_pipeline_dimensions(&image, &get_url_parameters("data/resize.jpg", "w=150")), (150, 100));
+        assert_eq!(get_pipeline_dimensions(&image, &get_url_parameters("data/resize.jpg", "h=100")), (150, 100));
+        assert_eq!(get_pipeline_dimensions(&image, &get_url_parameters("data/resize.jpg", "w=150&h=50")), (150, 50));
+        assert_eq!(get_pipeline_dimensions(&image, &get_url_parameters("data/resize.jpg", "w=150&rot=90")), (225, 150));
+        assert_eq!(get_pipeline_dimensions(&image, &get_url_parameters("data/resize.jpg", "w=150&rot=180")), (150, 100));
+
+        // Orientation is already applied in pipeline
+        assert_eq!(get_pipeline_dimensions(&image, &get_url_parameters("data/resize.jpg", "w=150&orient=6")), (150, 100));
+        assert_eq!(get_pipeline_dimensions(&image, &get_url_parameters("data/resize.jpg", "w=150&autorot=false")), (150, 100));
+    }
+
+    #[test]
+    fn test_get_rasterize_dimensions() {
+        let image = get_image(300, 200);
+
+        assert_eq!(get_rasterize_dimensions(&image, &get_url_parameters("data/resize.jpg", "w=150")), (150, 100));
+        assert_eq!(get_rasterize_dimensions(&image, &get_url_parameters("data/resize.jpg", "w=150&autorot=false")), (150, 100));
+
+        // Oriented image is 200x300, output 150x225 is swapped back to the unoriented image
+        for orientation in 5..=8 {
+            assert_eq!(get_rasterize_dimensions(&image, &get_url_parameters("data/resize.jpg", &format!("w=150&orient={orientation}"))), (225, 150));
+        }
 
-}
\ No newline at end of file
+        for orientation in 1..=4 {
+            assert_eq!(get_rasterize_dimensions(&image, &get_url_parameters("data/resize.jpg", &format!("w=150&orient={orientation}"))), (150, 100));
+        }
+
+        // Output 150x100 of the oriented and rotated image is swapped back by both rotation and orientation
+        assert_eq!(get_rasterize_dimensions(&image, &get_url_parameters("data/resize.jpg", "w=150&orient=6&rot=90")), (150, 100));
+    }
+
+    #[test]
+    fn test_get_rotated_dimensions() {
+        let (width, height) = get_rotated_dimensions((100, 50), Rotate::Angle(45.0));
+        assert!((width - 106.066).abs() < 0.001 && (height - 106.066).abs() < 0.001);
+
+        let (width, height) = get_rotated_dimensions((100, 50), Rotate::Left);
+        assert!((width - 50.0).abs() < 0.001 && (height - 100.0).abs() < 0.001);
+
+        assert_eq!(get_rotated_dimensions((100, 50), Rotate::No), (100.0, 50.0));
+    }
+}
@@ -1,17 +1,21 @@
 use libvips::{ops, VipsImage};
-use libvips::ops::Angle;
+use libvips::ops::{Angle, Direction, RotateOptions};
 
-use crate::parameters::{Rotate, UrlParameters};
-use crate::pipeline::{PipelineError, PipelineResult};
-use crate::services::vips::get_error_message;
+use crate::parameters::{Flip, Rotate, UrlParameters};
+use crate::pipeline::{background, PipelineError, PipelineResult};
+use crate::services::vips::{get_error_message, ImageHeader};
+
+const ORIENTATION_FIELD: &str = "orientation";
+const EXIF_ORIENTATION_FIELD: &str = "exif-ifd0-Orientation";
 
 pub(crate) async fn run(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
     
     let angle = match url_parameters.rotate {
-        Rotate::Right => Angle::D270,
+        Rotate::Left => Angle::D270,
         Rotate::UpsideDown => Angle::D180,
-        Rotate::Left => Angle::D90,
-        _ => Angle::D0
+        Rotate::Right => Angle::D90,
+        Rotate::Angle(angle) => return rotate(image, angle),
+        Rotate::No => Angle::D0
     };
 
     match ops::rot(&image, angle) {
@@ -21,9 +25,182 @@ pub(crate) async fn run(image: VipsImage, url_parameters: &UrlParameters<'_>) ->
 
 }
 
-pub(crate) async fn autorotate(image: VipsImage) -> PipelineResult<VipsImage> {
-    match ops::autorot(&image) {
+// Exposed corners are transparent, background is applied afterward like for any image with alpha channel
+fn rotate(image: VipsImage, angle: f64) -> PipelineResult<VipsImage> {
+
+    let image = background::add_alpha(image)?;
+
+    // libvips rotates clockwise, interpolation of premultiplied colors avoids dark edges
+    background::premultiplied(&image, |image| match ops::rotate_with_opts(image, -angle, &RotateOptions {
+        background: vec![0.0; image.get_bands() as usize],
+        ..RotateOptions::default()
+    }) {
+        Ok(rotated_image) => Ok(rotated_image),
+        Err(_) => Err(PipelineError(format!("Failed to rotate image: {}", get_error_message())))
+    })
+
+}
+
+pub(crate) async fn flip(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
+
+    let directions = match url_parameters.flip {
+        Flip::Horizontal => vec![Direction::Horizontal],
+        Flip::Vertical => vec![Direction::Vertical],
+        Flip::Both => vec![Direction::Horizontal, Direction::Vertical],
+        Flip::No => return Ok(image)
+    };
+
+    let mut image = image;
+
+    for direction in directions {
+        image = match ops::flip(&image, direction) {
+            Ok(image) => image,
+            Err(_) => return Err(PipelineError(format!("Failed to flip image: {}", get_error_message())))
+        };
+    }
+
+    Ok(image)
+
+}
+
+pub(crate) async fn autorotate(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
+
+    // Orientation override replaces the EXIF tag, disabled autorotate keeps raw pixel orientation
+    let orientation = match (url_parameters.orientation, url_parameters.autorotate) {
+        (Some(orientation), _) => orientation,
+        (None, true) => return match ops::autorot(&image) {
+            Ok(image) => Ok(image),
+            Err(_) => Err(PipelineError(format!("Failed to autorotate image: {}", get_error_message())))
+        },
+        (None, false) => 1
+    };
+
+    let image = orient(image, orientation)?;
+
+    // Tag is removed so that viewers do not rotate the image again
+    let mut header = match ImageHeader::new(&image) {
+        Some(header) => header,
+        None => return Err(PipelineError(format!("Failed to read image metadata: {}", get_error_message())))
+    };
+
+    header.remove(ORIENTATION_FIELD);
+    header.remove(EXIF_ORIENTATION_FIELD);
+
+    match header.apply(&image) {
+        Some(image) => Ok(image),
+        None => Err(PipelineError(format!("Failed to write image metadata: {}", get_error_message())))
+    }
+
+}
+
+// Apply EXIF orientation 1-8, rotation is applied before mirroring as in libvips autorot
+fn orient(image: VipsImage, orientation: u8) -> PipelineResult<VipsImage> {
+
+    let (angle, mirror) = match get_orientation_transform(orientation) {
+        Some(transform) => transform,
+        None => return Ok(image)
+    };
+
+    let image = match ops::rot(&image, angle) {
+        Ok(image) => image,
+        Err(_) => return Err(PipelineError(format!("Failed to orient image: {}", get_error_message())))
+    };
+
+    if !mirror {
+        return Ok(image);
+    }
+
+    match ops::flip(&image, Direction::Horizontal) {
         Ok(image) => Ok(image),
-        Err(_) => Err(PipelineError(format!("Failed to autorotate image: {}", get_error_message())))
+        Err(_) => Err(PipelineError(format!("Failed to orient image: {}", get_error_message())))
+    }
+
+}
+
+// Clockwise rotation and horizontal mirroring of EXIF orientation, None for orientation 1 and invalid values
+fn get_orientation_transform(orientation: u8) -> Option<(Angle, bool)> {
+    match orientation {
+        2 => Some((Angle::D0, true)),
+        3 => Some((Angle::D180, false)),
+        4 => Some((Angle::D180, true)),
+        5 => Some((Angle::D90, true)),
+        6 => Some((Angle::D90, false)),
+        7 => Some((Angle::D270, true)),
+        8 => Some((Angle::D270, false)),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libvips::ops::BandFormat;
+
+    use crate::pipeline::{get_url_parameters, init_vips};
+
+    use super::*;
+
+    // 3x2 image with pixel values 0-5 row by row
+    fn get_image() -> VipsImage {
+        VipsImage::new_from_memory(&[0, 1, 2, 3, 4, 5], 3, 2, 1, BandFormat::Uchar).unwrap()
+    }
+
+    // Dimensions and top left pixel value
+    fn describe(image: &VipsImage) -> (i32, i32, f64) {
+        (image.get_width(), image.get_height(), ops::getpoint(image, 0, 0).unwrap()[0])
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_get_orientation_transform() {
+        let transforms: Vec<Option<(i32, bool)>> = (0..=9)
+            .map(|orientation| get_orientation_transform(orientation).map(|(angle, mirror)| (angle as i32, mirror)))
+            .collect();
+
+        assert_eq!(transforms, vec![
+            None,
+            None,
+            Some((Angle::D0 as i32, true)),
+            Some((Angle::D180 as i32, false)),
+            Some((Angle::D180 as i32, true)),
+            Some((Angle::D90 as i32, true)),
+            Some((Angle::D90 as i32, false)),
+            Some((Angle::D270 as i32, true)),
+            Some((Angle::D270 as i32, false)),
+            None
+        ]);
+    }
+
+    #[test]
+    fn test_orient() {
+        init_vips();
+
+        let expected = [(3, 2, 0.0), (3, 2, 2.0), (3, 2, 5.0), (3, 2, 3.0), (2, 3, 0.0), (2, 3, 3.0), (2, 3, 5.0), (2, 3, 2.0)];
+
+        for (orientation, expected) in (1..=8).zip(expected) {
+            assert_eq!(describe(&orient(get_image(), orientation).unwrap()), expected, "orientation {orientation}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_autorotate() {
+        init_vips();
+
+        for (query, expected) in [("autorot=false", (3, 2, 0.0)), ("orient=6", (2, 3, 3.0)), ("autorot=false&orient=8", (2, 3, 2.0)), ("orient=9", (3, 2, 0.0))] {
+            let image = autorotate(get_image(), &get_url_parameters("data/orient.jpg", query)).await.unwrap();
+
+            assert_eq!(describe(&image), expected, "{query}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_flip() {
+        init_vips();
+
+        // Pixel values of the 3x2 image row by row after flipping
+        for (query, expected) in [("", [0.0, 1.0, 2.0, 3.0, 4.0, 5.0]), ("flip=h", [2.0, 1.0, 0.0, 5.0, 4.0, 3.0]), ("flip=v", [3.0, 4.0, 5.0, 0.0, 1.0, 2.0]), ("flip=both", [5.0, 4.0, 3.0, 2.0, 1.0, 0.0])] {
+            let image = flip(get_image(), &get_url_parameters("data/flip.jpg", query)).await.unwrap();
+            let pixels: Vec<f64> = (0..6).map(|index| ops::getpoint(&image, index % 3, index / 3).unwrap()[0]).collect();
+
+            assert_eq!(pixels, expected, "{query}");
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use libvips::{ops, VipsImage};
+use libvips::ops::{Align, BandFormat, CopyOptions, EmbedOptions, Interpretation, TextOptions};
+use log::debug;
+
+use crate::parameters::{Color, Origin, Text, UrlParameters};
+use crate::pipeline::{check, overlay, PipelineResult};
+use crate::services::fonts;
+
+const CONTEXT: &str = "render text";
+
+// Font size is given in pixels, at 72 DPI points equal pixels
+const TEXT_DPI: i32 = 72;
+
+pub(crate) async fn run(image: VipsImage, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
+
+    let text = match &url_parameters.text {
+        Some(text) => text,
+        None => return Ok(image)
+    };
+
+    let high_depth = matches!(image.get_format(), Ok(BandFormat::Ushort));
+    let (width, height) = (image.get_width(), image.get_height());
+
+    let label = render(text, width)?;
+    let label = overlay::to_depth(label, high_depth)?;
+
+    let (x, y) = text.origin.position((width, height), (label.get_width(), label.get_height()));
+
+    debug!("Placing {}x{} text at {x},{y}", label.get_width(), label.get_height());
+
+    overlay::composite(image, &label, x, y)
+
+}
+
+// Render text as sRGB image with alpha channel, including padding and background box
+fn render(text: &Text, width: i32) -> PipelineResult<VipsImage> {
+
+    let padding = text.padding as i32;
+    let font = format!("{} {}", fonts::resolve(&text.font), text.size);
+
+    debug!("Rendering text with font {font}");
+
+    // Text is wrapped to fit within the image including padding
+    let mask = check(ops::text_with_opts(&escape(&text.content), &TextOptions {
+        font,
+        width: (width - 2 * padding).max(1),
+        align: get_align(&text.origin),
+        dpi: TEXT_DPI,
+        ..TextOptions::default()
+    }), CONTEXT)?;
+
+    let label = colorize(&mask, &text.color)?;
+
+    let (label_width, label_height) = (label.get_width() + 2 * padding, label.get_height() + 2 * padding);
+
+    let label = check(ops::embed_with_opts(&label, padding, padding, label_width, label_height, &EmbedOptions {
+        background: vec![0.0; 4],
+        ..EmbedOptions::default()
+    }), CONTEXT)?;
+
+    let background = match &text.background {
+        Some(background) => background,
+        None => return Ok(label)
+    };
+
+    let mask = check(ops::black(label_width, label_height), CONTEXT)?;
+    let background = colorize(&check(ops::linear(&mask, &mut [0.0], &mut [255.0]), CONTEXT)?, background)?;
+
+    overlay::composite(background, &label, 0, 0)
+
+}
+
+// Fill color through the mask, mask scales alpha of the color
+fn colorize(mask: &VipsImage, color: &Color) -> PipelineResult<VipsImage> {
+
+    let color = Vec::from(color);
+
+    let rgb = check(ops::linear(mask, &mut [0.0, 0.0, 0.0], &mut color[0..3].to_vec()), CONTEXT)?;
+    let alpha = check(ops::linear(mask, &mut [color[3] / 255.0], &mut [0.0]), CONTEXT)?;
+
+    let label = check(ops::cast(&check(ops::bandjoin(&mut [rgb, alpha]), CONTEXT)?, BandFormat::Uchar), CONTEXT)?;
+
+    check(ops::copy_with_opts(&label, &CopyOptions {
+        interpretation: Interpretation::Srgb,
+        ..CopyOptions::default()
+    }), CONTEXT)
+
+}
+
+fn get_align(origin: &Origin) -> Align {
+    match origin {
+        Origin::TopLeft | Origin::LeftCenter | Origin::BottomLeft => Align::Low,
+        Origin::TopRight | Origin::RightCenter | Origin::BottomRight => Align::High,
+        _ => Align::Centre
+    }
+}
+
+// Text is rendered as Pango markup, user content must not be interpreted as markup
+fn escape(content: &str) -> String {
+    content
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -10,11 +10,21 @@ use crate::cache::{get_document_path_from_url_parameters, index};
 use crate::parameters::UrlParameters;
 use crate::pipeline::{PipelineError, PipelineResult};
 use crate::pipeline::resize::get_rasterize_dimensions;
-use crate::services::formats::{get_extension, is_thumbnail_format};
+use crate::services::detect::{detect, InputFormat};
+use crate::services::formats::{is_animated, is_thumbnail_format};
 use crate::services::vips::get_error_message;
 
+const TRIM_RENDER_SCALE: i32 = 2;
+
+const MAX_FRAMES: i32 = 1000;
+const MAX_ANIMATION_PIXELS: u64 = 100_000_000; // px in all frames
+
 pub(crate) async fn run(working_file: &Path, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
 
+    if is_animated(url_parameters.path) {
+        return load_animated(working_file, url_parameters);
+    }
+
     if !is_thumbnail_format(url_parameters.path) {
         return match VipsImage::new_from_file(&working_file.to_string_lossy()) {
             Ok(image) => Ok(image),
@@ -22,17 +32,64 @@ pub(crate) async fn run(working_file: &Path, url_parameters: &UrlParameters<'_>)
         };
     }
 
-    let extension = match get_extension(url_parameters.path) {
-        Ok(extension) => extension,
-        Err(_) => return Err(PipelineError("Failed to determine file extension".to_string()))
+    match detect(url_parameters.path) {
+        Some(InputFormat::Pdf) => generate_pdf_thumbnail(working_file, url_parameters),
+        Some(InputFormat::Document) => generate_document_thumbnail(working_file, url_parameters),
+        _ => Err(PipelineError("Unsupported file format".to_string()))
+    }
+
+}
+
+fn load_animated(working_file: &Path, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
+
+    // Frames are decoded lazily, only the header is read when opening
+    let image = match VipsImage::new_from_file(&(working_file.to_string_lossy() + "[n=-1]")) {
+        Ok(image) => image,
+        Err(error) => return Err(PipelineError(format!("Failed to open animated image: {}", error)))
     };
 
-    match extension.as_str() {
-        "pdf" => generate_pdf_thumbnail(working_file, url_parameters),
-        "doc" | "docx" | "odt" | "xls" | "xlsx" | "ods" | "ppt" | "pptx" | "odp" | "rtf" => generate_document_thumbnail(working_file, url_parameters),
-        _ => Err(PipelineError("Unsupported file format".to_string()))
+    let frames = image.get_n_pages().max(1);
+    let page_height = match image.get_page_height() {
+        page_height if page_height > 0 && image.get_height() % page_height == 0 => page_height,
+        _ => image.get_height()
+    };
+
+    // Requested frame only, frame page height equals its height, so it is not split into frames
+    if let Some(frame) = url_parameters.frame {
+        let page = (frame as i32 - 1).clamp(0, image.get_height() / page_height - 1);
+
+        return match ops::extract_area(&image, 0, page * page_height, image.get_width(), page_height) {
+            Ok(image) => Ok(image),
+            Err(_) => Err(PipelineError(format!("Failed to extract frame {frame}: {}", get_error_message())))
+        };
+    }
+
+    check_animation_limits(frames, image.get_width() as u64 * image.get_height() as u64, get_max_frames(), get_max_animation_pixels())?;
+    Ok(image)
+
+}
+
+// Every frame is processed separately, limits protect against animations with too many or too large frames
+fn check_animation_limits(frames: i32, pixels: u64, max_frames: i32, max_pixels: u64) -> PipelineResult<()> {
+
+    if frames > max_frames {
+        return Err(PipelineError(format!("Animated image has too many frames ({frames}, max. {max_frames})")));
     }
 
+    if pixels > max_pixels {
+        return Err(PipelineError(format!("Animated image is too large ({pixels} px in all frames, max. {max_pixels} px)")));
+    }
+
+    Ok(())
+
+}
+
+fn get_max_frames() -> i32 {
+    env::var("MAX_FRAMES").ok().and_then(|value| value.parse().ok()).unwrap_or(MAX_FRAMES)
+}
+
+fn get_max_animation_pixels() -> u64 {
+    env::var("MAX_ANIMATION_PIXELS").ok().and_then(|value| value.parse().ok()).unwrap_or(MAX_ANIMATION_PIXELS)
 }
 
 fn generate_pdf_thumbnail(working_file: &Path, url_parameters: &UrlParameters<'_>) -> PipelineResult<VipsImage> {
@@ -41,14 +98,26 @@ fn generate_pdf_thumbnail(working_file: &Path, url_parameters: &UrlParameters<'_
     let page_parameter = format!("[page={}]", (url_parameters.thumbnail.page - 1).min(pdf.get_n_pages() as u32 - 1));
 
     let pdf = VipsImage::new_from_file(&(working_file.to_string_lossy() + &page_parameter[..])).unwrap();
-    let (width, height) = get_rasterize_dimensions(&pdf, url_parameters);
-    
+    // Redacted regions refer to the page at its original size, the page is resized later
+    let (mut width, mut height) = match url_parameters.redact.is_some() {
+        true => (pdf.get_width(), pdf.get_height()),
+        false => get_rasterize_dimensions(&pdf, url_parameters)
+    };
+
+    // Trimmed pages are rendered uncropped in higher resolution, margins are removed and the content is resized later
+    let trim = url_parameters.trim.is_some();
+
+    if trim && url_parameters.redact.is_none() && (url_parameters.width.is_some() || url_parameters.height.is_some()) {
+        width *= TRIM_RENDER_SCALE;
+        height *= TRIM_RENDER_SCALE;
+    }
+
     match ops::thumbnail_with_opts(&(working_file.to_string_lossy() + &page_parameter[..]), width, &ThumbnailOptions {
         height,
         import_profile: "sRGB".to_string(),
         export_profile: "sRGB".to_string(),
         intent: Intent::Perceptual,
-        crop: Interesting::Centre,
+        crop: if trim || url_parameters.redact.is_some() { Interesting::None } else { Interesting::Centre },
         ..Default::default()
     }) {
         Ok(image) => Ok(image),
@@ -93,4 +162,18 @@ fn generate_pdf_from_document(working_file: &Path, cache_path: &Path) -> Pipelin
         Err(error) => Err(PipelineError(format!("Failed to convert document to PDF: {}", error)))
     }
     
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_animation_limits() {
+        assert!(check_animation_limits(1, 100, 10, 1000).is_ok());
+        assert!(check_animation_limits(10, 1000, 10, 1000).is_ok());
+        assert!(check_animation_limits(11, 100, 10, 1000).is_err());
+        assert!(check_animation_limits(2, 1001, 10, 1000).is_err());
+        assert!(check_animation_limits(MAX_FRAMES, MAX_ANIMATION_PIXELS, MAX_FRAMES, MAX_ANIMATION_PIXELS).is_ok());
+    }
+}
@@ -0,0 +1,72 @@
+use libvips::{ops, VipsImage};
+use libvips::ops::{BandFormat, FindTrimOptions, FlattenOptions, Interpretation};
+use log::debug;
+
+use crate::parameters::{Trim, UrlParameters};
+use crate::pipeline::{check, PipelineResult};
+
+const CONTEXT: &str = "trim image";
+
+// Crop uniform borders, the area is detected on the first frame and applied to all frames
+pub(crate) async fn run(frames: Vec<VipsImage>, url_parameters: &UrlParameters<'_>) -> PipelineResult<Vec<VipsImage>> {
+
+    let trim = match &url_parameters.trim {
+        Some(trim) => trim,
+        None => return Ok(frames)
+    };
+
+    let (left, top, width, height) = find_area(&frames[0], trim)?;
+
+    if width <= 0 || height <= 0 || (width == frames[0].get_width() && height == frames[0].get_height()) {
+        debug!("Nothing to trim");
+        return Ok(frames);
+    }
+
+    debug!("Trimming image to {width}x{height} at {left},{top}");
+    let mut trimmed_frames = Vec::with_capacity(frames.len());
+
+    for frame in frames {
+        trimmed_frames.push(check(ops::extract_area(&frame, left, top, width, height), CONTEXT)?);
+    }
+
+    Ok(trimmed_frames)
+
+}
+
+fn find_area(image: &VipsImage, trim: &Trim) -> PipelineResult<(i32, i32, i32, i32)> {
+
+    // Transparent borders are detected on alpha channel unless color is set
+    if image.image_hasalpha() && trim.color.is_none() {
+        let alpha = check(ops::extract_band(image, image.get_bands() - 1), CONTEXT)?;
+
+        return check(ops::find_trim_with_opts(&alpha, &FindTrimOptions {
+            threshold: trim.threshold * if matches!(alpha.get_format(), Ok(BandFormat::Ushort)) { 257.0 } else { 1.0 },
+            background: vec![0.0],
+            ..FindTrimOptions::default()
+        }), CONTEXT);
+    }
+
+    // Detection runs in 8-bit sRGB, so threshold and colors are independent on the source image
+    let image = check(ops::colourspace(image, Interpretation::Srgb), CONTEXT)?;
+
+    let background = match &trim.color {
+        Some(color) => Vec::from(color)[0..3].to_vec(),
+        None => check(ops::getpoint(&image, 0, 0), CONTEXT)?[0..3].to_vec()
+    };
+
+    // Transparent areas are considered as border of given color
+    let image = match image.image_hasalpha() {
+        true => check(ops::flatten_with_opts(&image, &FlattenOptions {
+            background: background.clone(),
+            ..FlattenOptions::default()
+        }), CONTEXT)?,
+        false => image
+    };
+
+    check(ops::find_trim_with_opts(&image, &FindTrimOptions {
+        threshold: trim.threshold,
+        background,
+        ..FindTrimOptions::default()
+    }), CONTEXT)
+
+}
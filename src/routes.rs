@@ -1,9 +1,11 @@
 use actix_web::web::ServiceConfig;
+use crate::services::capabilities::serve_capabilities;
 use crate::services::serve;
 
 pub fn routes(config: &mut ServiceConfig) {
     
     config
+        .service(serve_capabilities)
         .service(serve);
     
 }
\ No newline at end of file
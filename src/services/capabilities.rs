@@ -0,0 +1,291 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use actix_web::{get, HttpResponse, Responder};
+use actix_web::web::Query;
+use libvips::ops;
+use libvips::ops::{BlackOptions, ForeignHeifCompression, ForeignHeifEncoder, HeifsaveBufferOptions};
+use log::{debug, info, warn};
+use serde::Serialize;
+
+use crate::parameters::RawUrlParameters;
+use crate::services::detect::InputFormat;
+use crate::services::formats::OutputFormat;
+use crate::services::vips::{get_error_message, has_operation};
+
+const LOADERS: [&str; 13] = ["jpegload", "pngload", "gifload", "webpload", "tiffload", "svgload", "heifload", "jp2kload", "jxlload", "pdfload", "magickload", "openslideload", "matrixload"];
+const SAVERS: [&str; 8] = ["jpegsave", "pngsave", "gifsave", "webpsave", "tiffsave", "heifsave", "jxlsave", "magicksave"];
+// ffmpeg is only reported, no input format depends on it yet
+const TOOLS: [&str; 3] = ["soffice", "ffmpeg", "gs"];
+
+// libheif encoders, heifsave is available even when libheif was built without them
+const HEIF_ENCODERS: [(&str, ForeignHeifCompression, ForeignHeifEncoder); 4] = [
+    ("aom", ForeignHeifCompression::Av1, ForeignHeifEncoder::Aom),
+    ("rav1e", ForeignHeifCompression::Av1, ForeignHeifEncoder::Rav1E),
+    ("svt", ForeignHeifCompression::Av1, ForeignHeifEncoder::Svt),
+    ("x265", ForeignHeifCompression::Hevc, ForeignHeifEncoder::X265)
+];
+
+const PROBE_SIZE: i32 = 16;
+
+// Token of the capabilities endpoint is generated from this path, as for files
+const CAPABILITIES_PATH: &str = "_capabilities";
+
+static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+
+#[derive(Debug, Serialize)]
+pub struct Capabilities {
+    pub loaders: BTreeMap<&'static str, bool>,
+    pub savers: BTreeMap<&'static str, bool>,
+    pub encoders: BTreeMap<&'static str, bool>,
+    pub tools: BTreeMap<&'static str, bool>
+}
+
+impl Capabilities {
+
+    fn detect() -> Self {
+
+        let savers: BTreeMap<&'static str, bool> = SAVERS.iter().map(|saver| (*saver, has_operation("VipsForeignSave", saver))).collect();
+        let heifsave = *savers.get("heifsave").unwrap_or(&false);
+
+        Capabilities {
+            loaders: LOADERS.iter().map(|loader| (*loader, has_operation("VipsForeignLoad", loader))).collect(),
+            savers,
+            encoders: HEIF_ENCODERS.iter().map(|(name, compression, encoder)| (*name, heifsave && has_heif_encoder(*compression, *encoder))).collect(),
+            tools: TOOLS.iter().map(|tool| (*tool, has_executable(tool))).collect()
+        }
+
+    }
+
+    fn has_loader(&self, loader: &str) -> bool {
+        *self.loaders.get(loader).unwrap_or(&false)
+    }
+
+    fn has_saver(&self, saver: &str) -> bool {
+        *self.savers.get(saver).unwrap_or(&false)
+    }
+
+    fn has_tool(&self, tool: &str) -> bool {
+        *self.tools.get(tool).unwrap_or(&false)
+    }
+
+    pub fn has_encoder(&self, encoder: &str) -> bool {
+        *self.encoders.get(encoder).unwrap_or(&false)
+    }
+
+    // Requested libheif encoder when available for the compression, otherwise the first available one, e.g. get_heif_encoder(Some("svt"), ForeignHeifCompression::Av1)
+    pub fn get_heif_encoder(&self, requested: Option<&str>, compression: ForeignHeifCompression) -> ForeignHeifEncoder {
+        let mut available = HEIF_ENCODERS.iter()
+            .filter(|(name, encoder_compression, _)| *encoder_compression as i32 == compression as i32 && self.has_encoder(name));
+
+        available.clone()
+            .find(|(name, _, _)| Some(*name) == requested)
+            .or_else(|| available.next())
+            .map(|(_, _, encoder)| *encoder)
+            .unwrap_or(ForeignHeifEncoder::Auto)
+    }
+
+    fn has_av1_encoder(&self) -> bool {
+        self.has_encoder("aom") || self.has_encoder("rav1e") || self.has_encoder("svt")
+    }
+
+    // ImageMagick renders PDF through Ghostscript when libvips has no PDF loader
+    fn has_pdf_loader(&self) -> bool {
+        self.has_loader("pdfload") || (self.has_loader("magickload") && self.has_tool("gs"))
+    }
+
+    pub fn supports_input(&self, input_format: &InputFormat) -> bool {
+        match input_format {
+            InputFormat::Jpeg => self.has_loader("jpegload"),
+            InputFormat::Png => self.has_loader("pngload"),
+            InputFormat::Gif => self.has_loader("gifload"),
+            InputFormat::Webp => self.has_loader("webpload"),
+            InputFormat::Tiff => self.has_loader("tiffload"),
+            InputFormat::Svg => self.has_loader("svgload"),
+            InputFormat::Heif | InputFormat::Avif => self.has_loader("heifload"),
+            InputFormat::Jp2 => self.has_loader("jp2kload"),
+            InputFormat::Jxl => self.has_loader("jxlload"),
+            InputFormat::Pdf => self.has_pdf_loader(),
+            InputFormat::Document => self.has_pdf_loader() && self.has_tool("soffice"),
+            InputFormat::Bmp | InputFormat::Ico => self.has_loader("magickload"),
+            // Detected through an available libvips loader
+            InputFormat::Raster => true
+        }
+    }
+
+    pub fn supports_output(&self, output_format: &OutputFormat) -> bool {
+        match output_format {
+            OutputFormat::Jpg => self.has_saver("jpegsave"),
+            OutputFormat::Png => self.has_saver("pngsave"),
+            OutputFormat::Gif => self.has_saver("gifsave"),
+            OutputFormat::Webp => self.has_saver("webpsave"),
+            OutputFormat::Tiff => self.has_saver("tiffsave"),
+            OutputFormat::Avif => self.has_saver("heifsave") && self.has_av1_encoder(),
+            OutputFormat::Heic => self.has_saver("heifsave") && self.has_encoder("x265"),
+            OutputFormat::Jxl => self.has_saver("jxlsave"),
+            OutputFormat::Pdf => self.has_tool("soffice")
+        }
+    }
+
+}
+
+// Must be called after libvips is initialized
+pub fn init() -> &'static Capabilities {
+
+    let capabilities = get();
+
+    for (name, available) in capabilities.loaders.iter().chain(capabilities.savers.iter()).chain(capabilities.encoders.iter()).chain(capabilities.tools.iter()) {
+        match available {
+            true => info!("Capability {name}: available"),
+            false => warn!("Capability {name}: not available, formats depending on it are disabled")
+        }
+    }
+
+    capabilities
+
+}
+
+pub fn get() -> &'static Capabilities {
+    CAPABILITIES.get_or_init(Capabilities::detect)
+}
+
+// Encode a small image, libvips reports a missing encoder only when saving
+fn has_heif_encoder(compression: ForeignHeifCompression, encoder: ForeignHeifEncoder) -> bool {
+
+    let image = match ops::black_with_opts(PROBE_SIZE, PROBE_SIZE, &BlackOptions { bands: 3 }) {
+        Ok(image) => image,
+        Err(_) => return false
+    };
+
+    let result = ops::heifsave_buffer_with_opts(&image, &HeifsaveBufferOptions {
+        bitdepth: 8,
+        compression,
+        encoder,
+        effort: 0,
+        ..HeifsaveBufferOptions::default()
+    });
+
+    match result {
+        Ok(_) => true,
+        Err(_) => {
+            debug!("HEIF encoder {encoder:?} not available: {}", get_error_message());
+            false
+        }
+    }
+
+}
+
+fn has_executable(name: &str) -> bool {
+    let path = env::var("PATH").unwrap_or_default();
+
+    env::split_paths(&path).any(|directory| {
+        match Path::new(&directory).join(name).metadata() {
+            Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+            Err(_) => false
+        }
+    })
+}
+
+// Print capabilities report for `picturium doctor`
+pub fn doctor() {
+
+    let capabilities = get();
+    let sections = [("libvips loaders", &capabilities.loaders), ("libvips savers", &capabilities.savers), ("libheif encoders", &capabilities.encoders), ("external tools", &capabilities.tools)];
+
+    for (title, items) in sections {
+        println!("{title}:");
+
+        for (name, available) in items {
+            println!("  [{}] {name}", if *available { "x" } else { " " });
+        }
+    }
+
+}
+
+// Build information is exposed only when enabled, and only to signed requests when KEY is set
+#[get("/_capabilities")]
+pub async fn serve_capabilities(parameters: Query<HashMap<String, String>>, raw_url_parameters: Query<RawUrlParameters>) -> impl Responder {
+
+    if !is_exposed() {
+        return HttpResponse::NotFound().finish();
+    }
+
+    if let Err(e) = raw_url_parameters.verify_token(CAPABILITIES_PATH, &parameters) {
+        return HttpResponse::Forbidden().body(e);
+    }
+
+    HttpResponse::Ok().json(get())
+
+}
+
+fn is_exposed() -> bool {
+    env::var("CAPABILITIES_ENABLE").unwrap_or("false".to_string()) == "true"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_capabilities(loaders: &[&'static str], savers: &[&'static str], encoders: &[&'static str], tools: &[&'static str]) -> Capabilities {
+        Capabilities {
+            loaders: loaders.iter().map(|loader| (*loader, true)).collect(),
+            savers: savers.iter().map(|saver| (*saver, true)).collect(),
+            encoders: encoders.iter().map(|encoder| (*encoder, true)).collect(),
+            tools: tools.iter().map(|tool| (*tool, true)).collect()
+        }
+    }
+
+    #[test]
+    fn test_supports_input() {
+        let capabilities = get_capabilities(&["jpegload", "magickload"], &[], &[], &["soffice"]);
+        assert!(capabilities.supports_input(&InputFormat::Jpeg));
+        assert!(capabilities.supports_input(&InputFormat::Bmp));
+        assert!(capabilities.supports_input(&InputFormat::Raster));
+        assert!(!capabilities.supports_input(&InputFormat::Png));
+        assert!(!capabilities.supports_input(&InputFormat::Pdf));
+        assert!(!capabilities.supports_input(&InputFormat::Document));
+
+        let capabilities = get_capabilities(&["magickload"], &[], &[], &["soffice", "gs"]);
+        assert!(capabilities.supports_input(&InputFormat::Pdf));
+        assert!(capabilities.supports_input(&InputFormat::Document));
+
+        let capabilities = get_capabilities(&["pdfload"], &[], &[], &[]);
+        assert!(capabilities.supports_input(&InputFormat::Pdf));
+        assert!(!capabilities.supports_input(&InputFormat::Document));
+    }
+
+    #[test]
+    fn test_supports_output() {
+        let capabilities = get_capabilities(&[], &["jpegsave", "heifsave"], &[], &[]);
+        assert!(capabilities.supports_output(&OutputFormat::Jpg));
+        assert!(!capabilities.supports_output(&OutputFormat::Avif));
+        assert!(!capabilities.supports_output(&OutputFormat::Heic));
+        assert!(!capabilities.supports_output(&OutputFormat::Pdf));
+
+        let capabilities = get_capabilities(&[], &["heifsave"], &["svt"], &[]);
+        assert!(capabilities.supports_output(&OutputFormat::Avif));
+        assert!(!capabilities.supports_output(&OutputFormat::Heic));
+
+        let capabilities = get_capabilities(&[], &["heifsave"], &["x265"], &["soffice"]);
+        assert!(!capabilities.supports_output(&OutputFormat::Avif));
+        assert!(capabilities.supports_output(&OutputFormat::Heic));
+        assert!(capabilities.supports_output(&OutputFormat::Pdf));
+    }
+
+    #[test]
+    fn test_get_heif_encoder() {
+        let capabilities = get_capabilities(&[], &["heifsave"], &["rav1e", "svt", "x265"], &[]);
+        assert!(matches!(capabilities.get_heif_encoder(Some("svt"), ForeignHeifCompression::Av1), ForeignHeifEncoder::Svt));
+        assert!(matches!(capabilities.get_heif_encoder(Some("aom"), ForeignHeifCompression::Av1), ForeignHeifEncoder::Rav1E));
+        assert!(matches!(capabilities.get_heif_encoder(Some("x265"), ForeignHeifCompression::Av1), ForeignHeifEncoder::Rav1E));
+        assert!(matches!(capabilities.get_heif_encoder(None, ForeignHeifCompression::Hevc), ForeignHeifEncoder::X265));
+        assert!(matches!(capabilities.get_heif_encoder(Some("svt"), ForeignHeifCompression::Hevc), ForeignHeifEncoder::X265));
+
+        let capabilities = get_capabilities(&[], &["heifsave"], &[], &[]);
+        assert!(matches!(capabilities.get_heif_encoder(Some("x265"), ForeignHeifCompression::Hevc), ForeignHeifEncoder::Auto));
+    }
+
+}
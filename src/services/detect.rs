@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use log::debug;
+
+use crate::services::formats::get_extension;
+use crate::services::vips::find_loader;
+
+// Number of bytes read from the start of the file for content sniffing
+const SNIFF_SIZE: usize = 1024;
+const CACHE_CAPACITY: usize = 100_000;
+
+// Loaders of image formats, other libvips loaders (e.g. csv, matrix, vips, fits) would accept arbitrary files
+// ImageMagick is not a fallback, it loads PostScript, PSD, XCF and many more, BMP and ICO are detected from magic bytes
+const LOADER_PREFIX: &str = "VipsForeignLoad";
+const IMAGE_LOADERS: [&str; 14] = ["Jpeg", "Png", "Gif", "Nsgif", "Webp", "Tiff", "Heif", "Jp2k", "Jxl", "Svg", "Pdf", "Openexr", "Rad", "Ppm"];
+
+// Modification time, change time and size, files are considered modified as in cache::is_cached
+type FileVersion = (i64, i64, u64);
+
+type DetectionCache = Mutex<HashMap<PathBuf, (FileVersion, Option<InputFormat>)>>;
+
+static DETECTED: OnceLock<DetectionCache> = OnceLock::new();
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InputFormat {
+    Jpeg,
+    Png,
+    Gif,
+    Webp,
+    Bmp,
+    Tiff,
+    Ico,
+    Svg,
+    Heif,
+    Avif,
+    Jp2,
+    Jxl,
+    Pdf,
+    Document,
+    // Any other format libvips has a loader for
+    Raster
+}
+
+// Detect file format from its content, cached per file until it is modified
+pub fn detect(path: &Path) -> Option<InputFormat> {
+
+    let version = match path.metadata() {
+        Ok(metadata) => (metadata.mtime(), metadata.ctime(), metadata.size()),
+        Err(_) => return None
+    };
+
+    let cache = DETECTED.get_or_init(|| Mutex::new(HashMap::new()));
+
+    if let Some((cached_version, format)) = cache.lock().unwrap().get(path) {
+        if *cached_version == version {
+            return *format;
+        }
+    }
+
+    let format = detect_file(path);
+    debug!("Detected input format of {}: {format:?}", path.to_string_lossy());
+
+    let mut cache = cache.lock().unwrap();
+
+    if cache.len() >= CACHE_CAPACITY {
+        cache.clear();
+    }
+
+    cache.insert(path.to_path_buf(), (version, format));
+    format
+
+}
+
+fn detect_file(path: &Path) -> Option<InputFormat> {
+
+    let mut bytes = Vec::with_capacity(SNIFF_SIZE);
+
+    if let Ok(file) = File::open(path) {
+        let _ = file.take(SNIFF_SIZE as u64).read_to_end(&mut bytes);
+    }
+
+    let hint = get_extension(path).unwrap_or_default();
+
+    if let Some(format) = detect_bytes(&bytes, &hint) {
+        return Some(format);
+    }
+
+    // Fall back to libvips loaders for less common formats
+    get_loader_format(&find_loader(&path.to_string_lossy())?)
+
+}
+
+// Format of the file loaded by the libvips loader, e.g. VipsForeignLoadOpenexr, only image loaders are accepted
+fn get_loader_format(loader: &str) -> Option<InputFormat> {
+
+    let name = loader.strip_prefix(LOADER_PREFIX)?;
+
+    if !IMAGE_LOADERS.iter().any(|image_loader| name.starts_with(image_loader)) {
+        debug!("Ignoring non-image libvips loader {loader}");
+        return None;
+    }
+
+    Some(match name {
+        name if name.starts_with("Svg") => InputFormat::Svg,
+        name if name.starts_with("Pdf") => InputFormat::Pdf,
+        _ => InputFormat::Raster
+    })
+
+}
+
+// Match magic bytes, extension is used as a hint for container formats only
+pub fn detect_bytes(bytes: &[u8], hint: &str) -> Option<InputFormat> {
+
+    if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Some(InputFormat::Jpeg);
+    }
+
+    if bytes.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        return Some(InputFormat::Png);
+    }
+
+    if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        return Some(InputFormat::Gif);
+    }
+
+    if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        return Some(InputFormat::Webp);
+    }
+
+    if bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*") {
+        return Some(InputFormat::Tiff);
+    }
+
+    if bytes.starts_with(&[0x00, 0x00, 0x01, 0x00]) {
+        return Some(InputFormat::Ico);
+    }
+
+    if bytes.starts_with(b"%PDF") {
+        return Some(InputFormat::Pdf);
+    }
+
+    if bytes.starts_with(&[0xFF, 0x0A]) || bytes.starts_with(&[0x00, 0x00, 0x00, 0x0C, b'J', b'X', b'L', b' ', 0x0D, 0x0A, 0x87, 0x0A]) {
+        return Some(InputFormat::Jxl);
+    }
+
+    if bytes.starts_with(&[0x00, 0x00, 0x00, 0x0C, b'j', b'P', b' ', b' ', 0x0D, 0x0A, 0x87, 0x0A]) || bytes.starts_with(&[0xFF, 0x4F, 0xFF, 0x51]) {
+        return Some(InputFormat::Jp2);
+    }
+
+    if bytes.get(4..8) == Some(b"ftyp") {
+        return match bytes.get(8..12) {
+            Some(b"avif") | Some(b"avis") => Some(InputFormat::Avif),
+            Some(b"heic") | Some(b"heix") | Some(b"hevc") | Some(b"hevx") | Some(b"heim") | Some(b"heis") | Some(b"mif1") | Some(b"msf1") => Some(InputFormat::Heif),
+            _ => None
+        };
+    }
+
+    if bytes.starts_with(b"BM") {
+        return Some(InputFormat::Bmp);
+    }
+
+    // OLE2 compound file (doc, xls, ppt) and RTF
+    if bytes.starts_with(&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1]) || bytes.starts_with(b"{\\rtf") {
+        return Some(InputFormat::Document);
+    }
+
+    // ZIP container, either OpenDocument with mimetype as the first entry or Office Open XML
+    if bytes.starts_with(b"PK\x03\x04") {
+        let content = String::from_utf8_lossy(bytes);
+
+        if content.contains("application/vnd.oasis.opendocument") || matches!(hint, "docx" | "xlsx" | "pptx" | "odt" | "ods" | "odp") {
+            return Some(InputFormat::Document);
+        }
+
+        return None;
+    }
+
+    let content = String::from_utf8_lossy(bytes).to_lowercase();
+
+    if content.contains("<svg") {
+        return Some(InputFormat::Svg);
+    }
+
+    None
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_bytes() {
+        assert_eq!(detect_bytes(b"", ""), None);
+        assert_eq!(detect_bytes(b"plain text", "txt"), None);
+        assert_eq!(detect_bytes(&[0xFF, 0xD8, 0xFF, 0xE0], "png"), Some(InputFormat::Jpeg));
+        assert_eq!(detect_bytes(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A], "jpg"), Some(InputFormat::Png));
+        assert_eq!(detect_bytes(b"GIF89a", ""), Some(InputFormat::Gif));
+        assert_eq!(detect_bytes(b"RIFF\0\0\0\0WEBPVP8 ", ""), Some(InputFormat::Webp));
+        assert_eq!(detect_bytes(b"\0\0\0\x1cftypavif", ""), Some(InputFormat::Avif));
+        assert_eq!(detect_bytes(b"\0\0\0\x18ftypheic", ""), Some(InputFormat::Heif));
+        assert_eq!(detect_bytes(b"\0\0\0\x18ftypisom", "mp4"), None);
+        assert_eq!(detect_bytes(b"%PDF-1.7", ""), Some(InputFormat::Pdf));
+        assert_eq!(detect_bytes(b"<?xml version=\"1.0\"?>\n<SVG xmlns=\"http://www.w3.org/2000/svg\">", ""), Some(InputFormat::Svg));
+        assert_eq!(detect_bytes(b"PK\x03\x04\0\0mimetypeapplication/vnd.oasis.opendocument.text", ""), Some(InputFormat::Document));
+        assert_eq!(detect_bytes(b"PK\x03\x04\0\0[Content_Types].xml", "docx"), Some(InputFormat::Document));
+        assert_eq!(detect_bytes(b"PK\x03\x04\0\0archive", "zip"), None);
+    }
+
+    #[test]
+    fn test_get_loader_format() {
+        assert_eq!(get_loader_format("VipsForeignLoadMagick7File"), None);
+        assert_eq!(get_loader_format("VipsForeignLoadMagickBuffer"), None);
+        assert_eq!(get_loader_format("VipsForeignLoadOpenexr"), Some(InputFormat::Raster));
+        assert_eq!(get_loader_format("VipsForeignLoadSvgFile"), Some(InputFormat::Svg));
+        assert_eq!(get_loader_format("VipsForeignLoadPdfiumFile"), Some(InputFormat::Pdf));
+        assert_eq!(get_loader_format("VipsForeignLoadCsvFile"), None);
+        assert_eq!(get_loader_format("VipsForeignLoadMatrixFile"), None);
+        assert_eq!(get_loader_format("VipsForeignLoadVipsFile"), None);
+        assert_eq!(get_loader_format("VipsForeignLoadFitsFile"), None);
+        assert_eq!(get_loader_format("VipsForeignLoadRaw"), None);
+        assert_eq!(get_loader_format("Magick"), None);
+    }
+}
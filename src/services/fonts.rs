@@ -0,0 +1,52 @@
+use std::collections::HashSet;
+use std::env;
+use std::process::Command;
+use std::sync::OnceLock;
+
+use log::{info, warn};
+
+static FONTS: OnceLock<HashSet<String>> = OnceLock::new();
+
+// Font families known to fontconfig, lowercase, including localized family names
+fn detect() -> HashSet<String> {
+
+    let output = match Command::new("fc-list").arg(":").arg("family").output() {
+        Ok(output) if output.status.success() => output,
+        _ => {
+            warn!("Failed to list installed fonts, only the default font is available for text overlays");
+            return HashSet::new();
+        }
+    };
+
+    let fonts: HashSet<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .flat_map(|line| line.split(','))
+        .map(|family| family.trim().to_lowercase())
+        .filter(|family| !family.is_empty())
+        .collect();
+
+    info!("Found {} installed font families", fonts.len());
+    fonts
+
+}
+
+// Fonts are listed at startup instead of during the first request with text
+pub fn init() {
+    FONTS.get_or_init(detect);
+}
+
+pub fn is_installed(family: &str) -> bool {
+    FONTS.get_or_init(detect).contains(&family.to_lowercase())
+}
+
+pub fn get_default() -> String {
+    env::var("TEXT_FONT").ok().filter(|font| !font.is_empty()).unwrap_or("sans".to_string())
+}
+
+// Fonts which are not installed fall back to the default font instead of the Pango substitution
+pub fn resolve(family: &Option<String>) -> String {
+    match family {
+        Some(family) if is_installed(family) => family.clone(),
+        _ => get_default()
+    }
+}
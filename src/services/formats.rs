@@ -2,11 +2,14 @@ use std::env;
 use std::fmt::Display;
 use std::path::Path;
 use actix_web::http::header::HeaderValue;
+use libvips::ops::BandFormat;
 use libvips::VipsImage;
 use log::{error, warn};
 use crate::parameters::format::Format;
 use crate::parameters::UrlParameters;
-use crate::pipeline::{PipelineError, PipelineResult};   
+use crate::pipeline::{PipelineError, PipelineResult};
+use crate::services::capabilities;
+use crate::services::detect::{detect, InputFormat};   
 
 const WEBP_MAX_WIDTH: i32 = 16383; // px
 const WEBP_MAX_HEIGHT: i32 = 16383; // px
@@ -18,12 +21,27 @@ const AVIF_MAX_HEIGHT: i32 = 16384; // px
 const PNG_MAX_WIDTH: i32 = 16384; // px
 const PNG_MAX_HEIGHT: i32 = 16384; // px
 
+const JXL_MAX_WIDTH: i32 = 1073741823; // px
+const JXL_MAX_HEIGHT: i32 = 1073741823; // px
+
+const GIF_MAX_WIDTH: i32 = 65535; // px
+const GIF_MAX_HEIGHT: i32 = 65535; // px
+
+const HEIC_MAX_WIDTH: i32 = 16384; // px
+const HEIC_MAX_HEIGHT: i32 = 16384; // px
+
+const TIFF_MAX_SIZE: f64 = 4294967295.0; // B, uncompressed size limit of classic TIFF
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum OutputFormat {
     Avif,
     Webp,
     Jpg,
     Png,
+    Jxl,
+    Gif,
+    Tiff,
+    Heic,
     Pdf
 }
 
@@ -34,6 +52,10 @@ impl Display for OutputFormat {
             OutputFormat::Webp => write!(f, "webp"),
             OutputFormat::Jpg => write!(f, "jpg"),
             OutputFormat::Png => write!(f, "png"),
+            OutputFormat::Jxl => write!(f, "jxl"),
+            OutputFormat::Gif => write!(f, "gif"),
+            OutputFormat::Tiff => write!(f, "tiff"),
+            OutputFormat::Heic => write!(f, "heic"),
             OutputFormat::Pdf => write!(f, "pdf")
         }
     }
@@ -50,29 +72,39 @@ pub fn get_extension(path: &Path) -> Result<String, ()> {
 }
 
 pub fn check_supported_input_formats(path: &Path) -> Result<(), ()> {
+    match detect(path) {
+        Some(input_format) if capabilities::get().supports_input(&input_format) => Ok(()),
+        _ => Err(())
+    }
+}
 
-    let extension = get_extension(path)?;
+pub fn determine_output_format(url_parameters: &UrlParameters, accept: Option<&HeaderValue>) -> OutputFormat {
 
-    match extension.as_str() {
-        "jpg" | "jpeg" | "png" | "webp" | "gif" | "bmp" | "tif" | "tiff" | "ico" | "svg" | // Raster formats
-        "heic" | "heif" | "jp2" | "jpm" | "jpx" | "jpf" | "avif" | "avifs" | // Modern raster formats
-        // "arw" | "raw" | // RAW formats
-        "doc" | "docx" | "odt" | "xls" | "xlsx" | "ods" | "ppt" | "pptx" | "odp" | "rtf" | // Document formats
-        "pdf" // Document formats
-        => Ok(()),
-        _ => Err(())
+    let output_format = negotiate_output_format(url_parameters, accept);
+
+    if capabilities::get().supports_output(&output_format) {
+        return output_format;
     }
 
+    warn!("Output format {output_format} is not supported by the installed libvips, falling back to JPEG");
+    OutputFormat::Jpg
+
 }
 
-pub fn determine_output_format(url_parameters: &UrlParameters, accept: Option<&HeaderValue>) -> OutputFormat {
-    
+fn negotiate_output_format(url_parameters: &UrlParameters, accept: Option<&HeaderValue>) -> OutputFormat {
+
+    let capabilities = capabilities::get();
+
     if url_parameters.format != Format::Auto {
         return match url_parameters.format.as_str() {
             "jpg" => OutputFormat::Jpg,
             "png" => OutputFormat::Png,
             "webp" => OutputFormat::Webp,
             "avif" => OutputFormat::Avif,
+            "jxl" => OutputFormat::Jxl,
+            "gif" => OutputFormat::Gif,
+            "tiff" => OutputFormat::Tiff,
+            "heic" => OutputFormat::Heic,
             "pdf" => OutputFormat::Pdf,
             _ => OutputFormat::Webp
         }
@@ -88,42 +120,68 @@ pub fn determine_output_format(url_parameters: &UrlParameters, accept: Option<&H
         Err(_) => return OutputFormat::Webp
     };
 
-    if env::var("AVIF_ENABLE").unwrap_or("false".to_string()) == "true" && accept.contains("image/avif") {
+    if env::var("JXL_ENABLE").unwrap_or("false".to_string()) == "true" && accept.contains("image/jxl") && capabilities.supports_output(&OutputFormat::Jxl) {
+        return OutputFormat::Jxl;
+    }
+
+    if env::var("AVIF_ENABLE").unwrap_or("false".to_string()) == "true" && accept.contains("image/avif") && capabilities.supports_output(&OutputFormat::Avif) {
         return OutputFormat::Avif;
     }
 
-    if accept.contains("image/webp") {
+    if accept.contains("image/webp") && capabilities.supports_output(&OutputFormat::Webp) {
         return OutputFormat::Webp;
     }
 
+    // Shape masks need transparency, explicitly requested JPEG is flattened instead
+    if has_mask(url_parameters) && capabilities.supports_output(&OutputFormat::Png) {
+        return OutputFormat::Png;
+    }
+
     OutputFormat::Jpg
 
 }
 
 pub fn is_thumbnail_format(path: &Path) -> bool {
-    let extension = get_extension(path).unwrap_or_else(|_| String::new());
-    matches!(extension.as_str(), "pdf" | "doc" | "docx" | "odt" | "xls" | "xlsx" | "ods" | "ppt" | "pptx" | "odp" | "rtf")
+    matches!(detect(path), Some(InputFormat::Pdf) | Some(InputFormat::Document))
 }
 
 pub fn is_svg(path: &Path) -> bool {
-    let extension = get_extension(path).unwrap_or_else(|_| String::new());
-    extension == "svg"
+    detect(path) == Some(InputFormat::Svg)
 }
 
 pub fn is_generated(path: &Path) -> bool {
-    let extension = get_extension(path).unwrap_or_else(|_| String::new());
-    matches!(extension.as_str(), "doc" | "docx" | "odt" | "xls" | "xlsx" | "ods" | "ppt" | "pptx" | "odp" | "rtf")
+    detect(path) == Some(InputFormat::Document)
+}
+
+pub fn is_animated(path: &Path) -> bool {
+    matches!(detect(path), Some(InputFormat::Gif) | Some(InputFormat::Webp))
+}
+
+pub fn supports_animation(output_format: &OutputFormat) -> bool {
+    matches!(output_format, OutputFormat::Gif | OutputFormat::Webp | OutputFormat::Avif)
 }
 
-pub fn supports_transparency(path: &Path) -> bool {
-    let extension = get_extension(path).unwrap_or_else(|_| String::new());
-    !matches!(extension.as_str(), "jpg" | "jpeg")
+// Bit depth supported by the output format closest to the requested one
+pub fn get_output_depth(output_format: &OutputFormat, depth: u8) -> u8 {
+    match output_format {
+        OutputFormat::Avif | OutputFormat::Heic => depth.min(12),
+        OutputFormat::Png | OutputFormat::Tiff if depth > 8 => 16,
+        _ => 8
+    }
+}
+
+pub fn has_mask(url_parameters: &UrlParameters<'_>) -> bool {
+    url_parameters.radius.is_some() || url_parameters.mask.is_some()
+}
+
+pub fn supports_alpha(output_format: &OutputFormat) -> bool {
+    !matches!(output_format, OutputFormat::Jpg | OutputFormat::Pdf)
 }
 
 pub fn validate_output_format(image: &VipsImage, url_parameters: &UrlParameters<'_>, output_format: &OutputFormat) -> PipelineResult<OutputFormat> {
     match output_format {
         OutputFormat::Webp => {
-            let (width, height) = (image.get_width(), image.get_height());
+            let (width, height) = get_output_dimensions(image, url_parameters);
             let downsize = width > WEBP_MAX_WIDTH || height > WEBP_MAX_HEIGHT || (width * height) as f64 > (WEBP_MAX_RESOLUTION * 1_000_000.0);
 
             if !downsize {
@@ -137,13 +195,13 @@ pub fn validate_output_format(image: &VipsImage, url_parameters: &UrlParameters<
 
             warn!("Very large image, falling back to JPEG/PNG format");
 
-            Ok(match image.image_hasalpha() && width <= PNG_MAX_WIDTH && height <= PNG_MAX_HEIGHT {
+            Ok(match (image.image_hasalpha() || has_mask(url_parameters)) && width <= PNG_MAX_WIDTH && height <= PNG_MAX_HEIGHT {
                 true => OutputFormat::Png,
                 false => OutputFormat::Jpg,
             })
         },
         OutputFormat::Avif => {
-            let (width, height) = (image.get_width(), image.get_height());
+            let (width, height) = get_output_dimensions(image, url_parameters);
             let downsize = width > AVIF_MAX_WIDTH || height > AVIF_MAX_HEIGHT;
 
             if !downsize {
@@ -159,7 +217,7 @@ pub fn validate_output_format(image: &VipsImage, url_parameters: &UrlParameters<
             Ok(OutputFormat::Jpg)
         },
         OutputFormat::Png => {
-            let (width, height) = (image.get_width(), image.get_height());
+            let (width, height) = get_output_dimensions(image, url_parameters);
             let downsize = width > PNG_MAX_WIDTH || height > PNG_MAX_HEIGHT;
 
             if !downsize {
@@ -174,6 +232,122 @@ pub fn validate_output_format(image: &VipsImage, url_parameters: &UrlParameters<
             warn!("Very large image, falling back to JPEG format");
             Ok(OutputFormat::Jpg)
         },
+        OutputFormat::Jxl => {
+            let (width, height) = get_output_dimensions(image, url_parameters);
+            let downsize = width > JXL_MAX_WIDTH || height > JXL_MAX_HEIGHT;
+
+            if !downsize {
+                return Ok(output_format.clone());
+            }
+
+            if url_parameters.format != Format::Auto {
+                error!("JXL output image is too large (max. {JXL_MAX_WIDTH}x{JXL_MAX_HEIGHT})");
+                return Err(PipelineError("Failed to save image: too large".to_string()));
+            }
+
+            warn!("Very large image, falling back to JPEG format");
+            Ok(OutputFormat::Jpg)
+        },
+        OutputFormat::Gif => {
+            let (width, height) = get_output_dimensions(image, url_parameters);
+            let downsize = width > GIF_MAX_WIDTH || height > GIF_MAX_HEIGHT;
+
+            if !downsize {
+                return Ok(output_format.clone());
+            }
+
+            if url_parameters.format != Format::Auto {
+                error!("GIF output image is too large (max. {GIF_MAX_WIDTH}x{GIF_MAX_HEIGHT})");
+                return Err(PipelineError("Failed to save image: too large".to_string()));
+            }
+
+            warn!("Very large image, falling back to JPEG format");
+            Ok(OutputFormat::Jpg)
+        },
+        OutputFormat::Heic => {
+            let (width, height) = get_output_dimensions(image, url_parameters);
+            let downsize = width > HEIC_MAX_WIDTH || height > HEIC_MAX_HEIGHT;
+
+            if !downsize {
+                return Ok(output_format.clone());
+            }
+
+            if url_parameters.format != Format::Auto {
+                error!("HEIC output image is too large (max. {HEIC_MAX_WIDTH}x{HEIC_MAX_HEIGHT})");
+                return Err(PipelineError("Failed to save image: too large".to_string()));
+            }
+
+            warn!("Very large image, falling back to JPEG format");
+            Ok(OutputFormat::Jpg)
+        },
+        OutputFormat::Tiff => {
+            if requires_bigtiff(image) {
+                warn!("Very large image, saving as BigTIFF");
+            }
+
+            Ok(output_format.clone())
+        },
         _ => Ok(output_format.clone())
     }
-}
\ No newline at end of file
+}
+
+// Dimensions of the image extended by padding and canvas
+fn get_output_dimensions(image: &VipsImage, url_parameters: &UrlParameters<'_>) -> (i32, i32) {
+
+    let (mut width, mut height) = (image.get_width(), image.get_height());
+
+    if let Some(padding) = &url_parameters.padding {
+        let (top, right, bottom, left) = padding.resolve(width, height);
+        width += left + right;
+        height += top + bottom;
+    }
+
+    if let Some(canvas) = &url_parameters.canvas {
+        width = canvas.width as i32;
+        height = canvas.height as i32;
+    }
+
+    (width, height)
+
+}
+
+pub fn requires_bigtiff(image: &VipsImage) -> bool {
+    let sample_size = get_sample_size(image.get_format().unwrap_or(BandFormat::Uchar));
+    image.get_width() as f64 * image.get_height() as f64 * image.get_bands() as f64 * sample_size > TIFF_MAX_SIZE
+}
+
+// Bytes per sample of the band format
+fn get_sample_size(format: BandFormat) -> f64 {
+    match format {
+        BandFormat::Ushort | BandFormat::Short => 2.0,
+        BandFormat::Uint | BandFormat::Int | BandFormat::Float => 4.0,
+        BandFormat::Complex | BandFormat::Double => 8.0,
+        BandFormat::Dpcomplex => 16.0,
+        _ => 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libvips::ops;
+    use libvips::ops::BlackOptions;
+
+    use crate::pipeline::init_vips;
+
+    use super::*;
+
+    // Pixels are computed lazily, large images are never allocated
+    fn get_image(width: i32, height: i32, bands: i32, format: BandFormat) -> VipsImage {
+        let image = ops::black_with_opts(width, height, &BlackOptions { bands }).unwrap();
+        ops::cast(&image, format).unwrap()
+    }
+
+    #[test]
+    fn test_requires_bigtiff() {
+        init_vips();
+        assert!(!requires_bigtiff(&get_image(30000, 30000, 3, BandFormat::Uchar)));
+        assert!(requires_bigtiff(&get_image(30000, 30000, 3, BandFormat::Ushort)));
+        assert!(requires_bigtiff(&get_image(40000, 40000, 3, BandFormat::Uchar)));
+        assert!(!requires_bigtiff(&get_image(1000, 1000, 4, BandFormat::Double)));
+    }
+}
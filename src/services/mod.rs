@@ -18,6 +18,11 @@ use crate::services::formats::is_generated;
 pub mod formats;
 pub mod vips;
 pub mod scheduler;
+pub mod profiles;
+pub mod detect;
+pub mod capabilities;
+pub mod watermarks;
+pub mod fonts;
 
 #[get("{path:.*}")]
 pub async fn serve(req: HttpRequest, path: Path<String>, parameters: Query<HashMap<String, String>>, raw_url_parameters: Query<RawUrlParameters>) -> impl Responder {
@@ -28,6 +33,11 @@ pub async fn serve(req: HttpRequest, path: Path<String>, parameters: Query<HashM
         return HttpResponse::Forbidden().body(e);
     }
 
+    // Paths cannot be served without mandatory watermarks when they failed to load
+    if watermarks::init().is_err() {
+        return HttpResponse::ServiceUnavailable().into();
+    }
+
     let url_parameters = UrlParameters::new(&path, raw_url_parameters.into_inner());
 
     // Check if original file exists
@@ -35,8 +45,14 @@ pub async fn serve(req: HttpRequest, path: Path<String>, parameters: Query<HashM
         return HttpResponse::NotFound().into();
     }
 
-    // Serve original image or file
-    if url_parameters.original || formats::check_supported_input_formats(url_parameters.path).is_err() {
+    let protected = url_parameters.redact.is_some() || url_parameters.mandatory_watermark.is_some();
+
+    // Serve original image or file, redacted and watermarked images are never served as original
+    if protected && formats::check_supported_input_formats(url_parameters.path).is_err() {
+        return HttpResponse::Forbidden().into();
+    }
+
+    if (url_parameters.original && !protected) || formats::check_supported_input_formats(url_parameters.path).is_err() {
         return match NamedFile::open(url_parameters.path) {
             Ok(named_file) => {
                 let mut response = NamedFile::into_response(named_file.prefer_utf8(true), &req);
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::sync::OnceLock;
+
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::parameters::UrlParameters;
+
+static PROFILES: OnceLock<ProfilesConfig> = OnceLock::new();
+
+const QUALITY_RANGE: (i32, i32) = (1, 100);
+const ENCODERS: [&str; 5] = ["auto", "aom", "rav1e", "svt", "x265"];
+
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+pub struct ProfilesConfig {
+    default: Option<String>,
+    profiles: HashMap<String, Profile>,
+    paths: HashMap<String, String>,
+    #[serde(skip)]
+    fallback: Profile
+}
+
+#[derive(Default, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Profile {
+    pub avif: EncoderProfile,
+    pub heic: EncoderProfile,
+    pub webp: EncoderProfile,
+    pub jpg: EncoderProfile,
+    pub png: EncoderProfile,
+    pub jxl: EncoderProfile,
+    pub gif: EncoderProfile
+}
+
+// Unset values fall back to the defaults of each encoder in finalize
+#[derive(Default, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EncoderProfile {
+    pub effort: Option<i32>,
+    pub bitdepth: Option<i32>,
+    pub subsample: Option<String>,
+    pub encoder: Option<String>,
+    pub quality_min: Option<i32>,
+    pub quality_max: Option<i32>,
+    pub alpha_quality: Option<i32>
+}
+
+impl ProfilesConfig {
+
+    fn parse(content: &str) -> serde_json::Result<ProfilesConfig> {
+
+        let mut config: ProfilesConfig = serde_json::from_str(content)?;
+
+        for (name, profile) in config.profiles.iter_mut() {
+            profile.validate(name);
+        }
+
+        Ok(config)
+
+    }
+
+    // Profile selection order: requested profile, longest matching path prefix, configured default
+    fn resolve(&self, requested: Option<&String>, path: &str) -> &Profile {
+
+        let path_profile = self.paths.iter()
+            .filter(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, name)| name);
+
+        let name = match requested.or(path_profile).or(self.default.as_ref()) {
+            Some(name) => name,
+            None => return &self.fallback
+        };
+
+        match self.profiles.get(name) {
+            Some(profile) => profile,
+            None => {
+                warn!("Unknown encoding profile {name}, using default encoder settings");
+                &self.fallback
+            }
+        }
+
+    }
+
+}
+
+impl Profile {
+
+    // Ranges of libvips savers, values out of range are clamped, unsupported values are removed
+    fn validate(&mut self, name: &str) {
+        self.avif.validate(name, "avif", Some((0, 9)), &[8, 10, 12]);
+        self.heic.validate(name, "heic", Some((0, 9)), &[8, 10, 12]);
+        self.webp.validate(name, "webp", Some((0, 6)), &[]);
+        self.jpg.validate(name, "jpg", None, &[]);
+        self.png.validate(name, "png", Some((1, 10)), &[1, 2, 4, 8, 16]);
+        self.jxl.validate(name, "jxl", Some((1, 9)), &[]);
+        self.gif.validate(name, "gif", Some((1, 10)), &[1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+}
+
+impl EncoderProfile {
+
+    fn validate(&mut self, name: &str, format: &str, effort: Option<(i32, i32)>, bitdepths: &[i32]) {
+
+        let context = format!("encoding profile {name}.{format}");
+
+        self.effort = match (self.effort, effort) {
+            (Some(value), Some(range)) => Some(clamp(value, range, &format!("{context}.effort"))),
+            (Some(_), None) => {
+                warn!("Ignoring effort of {context}, the encoder has no effort setting");
+                None
+            },
+            (None, _) => None
+        };
+
+        self.bitdepth = match self.bitdepth {
+            Some(bitdepth) if !bitdepths.contains(&bitdepth) => {
+                warn!("Ignoring unsupported bitdepth {bitdepth} of {context}");
+                None
+            },
+            bitdepth => bitdepth
+        };
+
+        self.encoder = match self.encoder.take() {
+            Some(encoder) if !ENCODERS.contains(&encoder.as_str()) => {
+                warn!("Ignoring unknown encoder {encoder} of {context}");
+                None
+            },
+            encoder => encoder
+        };
+
+        self.quality_min = self.quality_min.map(|quality| clamp(quality, QUALITY_RANGE, &format!("{context}.quality_min")));
+        self.quality_max = self.quality_max.map(|quality| clamp(quality, QUALITY_RANGE, &format!("{context}.quality_max")));
+        self.alpha_quality = self.alpha_quality.map(|quality| clamp(quality, (0, 100), &format!("{context}.alpha_quality")));
+
+        if let (Some(min), Some(max)) = (self.quality_min, self.quality_max) {
+            if min > max {
+                warn!("Minimum quality of {context} is higher than maximum quality, using {max}");
+                self.quality_min = Some(max);
+            }
+        }
+
+    }
+
+}
+
+fn clamp(value: i32, (min, max): (i32, i32), name: &str) -> i32 {
+    let clamped = value.clamp(min, max);
+
+    if clamped != value {
+        warn!("Value {value} of {name} is out of range {min}-{max}, using {clamped}");
+    }
+
+    clamped
+}
+
+fn load() -> ProfilesConfig {
+
+    let path = match env::var("PROFILES") {
+        Ok(path) if !path.is_empty() => path,
+        _ => return ProfilesConfig::default()
+    };
+
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) => {
+            error!("Failed to read encoding profiles from {path}: {e}");
+            return ProfilesConfig::default();
+        }
+    };
+
+    match ProfilesConfig::parse(&content) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("Failed to parse encoding profiles from {path}: {e}");
+            ProfilesConfig::default()
+        }
+    }
+
+}
+
+pub fn config() -> &'static ProfilesConfig {
+    PROFILES.get_or_init(load)
+}
+
+pub fn get(url_parameters: &UrlParameters<'_>) -> &'static Profile {
+    config().resolve(url_parameters.profile.as_ref(), &url_parameters.path.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"{
+        "default": "balanced",
+        "profiles": {
+            "fast": { "avif": { "effort": 0 }, "jpg": { "quality_max": 70 } },
+            "balanced": { "avif": { "effort": 4 } },
+            "invalid": {
+                "avif": { "effort": 100, "bitdepth": 9, "encoder": "unknown", "quality_min": 90, "quality_max": 50 },
+                "webp": { "effort": -1, "alpha_quality": 200 },
+                "jpg": { "effort": 5, "quality_min": 0, "quality_max": 1000 }
+            }
+        },
+        "paths": { "data/": "fast", "data/archive/": "invalid" }
+    }"#;
+
+    #[test]
+    fn test_profiles_validate() {
+        let config = ProfilesConfig::parse(CONFIG).unwrap();
+        let profile = &config.profiles["invalid"];
+
+        assert_eq!((profile.avif.effort, profile.avif.bitdepth, profile.avif.encoder.as_deref()), (Some(9), None, None));
+        assert_eq!((profile.avif.quality_min, profile.avif.quality_max), (Some(50), Some(50)));
+        assert_eq!((profile.webp.effort, profile.webp.alpha_quality), (Some(0), Some(100)));
+        assert_eq!((profile.jpg.effort, profile.jpg.quality_min, profile.jpg.quality_max), (None, Some(1), Some(100)));
+        assert_eq!(config.profiles["fast"].avif.effort, Some(0));
+    }
+
+    #[test]
+    fn test_profiles_resolve() {
+        let config = ProfilesConfig::parse(CONFIG).unwrap();
+
+        assert_eq!(config.resolve(None, "data/image.jpg").jpg.quality_max, Some(70));
+        assert_eq!(config.resolve(None, "data/archive/image.jpg").avif.effort, Some(9));
+        assert_eq!(config.resolve(None, "image.jpg").avif.effort, Some(4));
+        assert_eq!(config.resolve(Some(&"balanced".to_string()), "data/image.jpg").avif.effort, Some(4));
+        assert_eq!(config.resolve(Some(&"unknown".to_string()), "data/image.jpg").avif.effort, None);
+        assert_eq!(ProfilesConfig::default().resolve(None, "data/image.jpg").avif.effort, None);
+    }
+
+}
@@ -1,5 +1,8 @@
-use std::ffi::CStr;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::{ptr, slice};
+
 use libvips::bindings::{vips_error_buffer, vips_error_clear};
+use libvips::{bindings, ops, VipsImage};
 
 pub fn get_error_message() -> String {
     unsafe {
@@ -10,3 +13,171 @@ pub fn get_error_message() -> String {
         format!("{:?}", error_message)
     }
 }
+
+// Metadata is copied through the libvips native format, which keeps all fields including blobs
+const HEADER_FORMAT: &CStr = c".v";
+
+// Metadata of an image held on its 1x1 copy owned through the libvips C API, libvips-rs has no metadata API
+pub struct ImageHeader {
+    image: *mut bindings::VipsImage,
+    // Loaded image refers to the buffer, so it must live as long as the image
+    _buffer: Vec<u8>
+}
+
+impl ImageHeader {
+
+    pub fn new(image: &VipsImage) -> Option<ImageHeader> {
+        let pixel = ops::extract_area(image, 0, 0, 1, 1).ok()?;
+        let buffer = pixel.image_write_to_buffer(&HEADER_FORMAT.to_string_lossy()).ok()?;
+
+        let header = unsafe {
+            bindings::vips_image_new_from_buffer(buffer.as_ptr() as *const c_void, buffer.len() as bindings::size_t, c"".as_ptr(), ptr::null::<c_char>())
+        };
+
+        if header.is_null() {
+            unsafe { vips_error_clear() };
+            return None;
+        }
+
+        Some(ImageHeader { image: header, _buffer: buffer })
+    }
+
+    pub fn get_fields(&self) -> Vec<String> {
+        unsafe {
+            let fields = bindings::vips_image_get_fields(self.image);
+            let mut names = Vec::new();
+            let mut index = 0;
+
+            while !(*fields.add(index)).is_null() {
+                names.push(CStr::from_ptr(*fields.add(index)).to_string_lossy().into_owned());
+                index += 1;
+            }
+
+            bindings::g_strfreev(fields);
+            names
+        }
+    }
+
+    pub fn get_string(&self, name: &str) -> Option<String> {
+        let name = CString::new(name).ok()?;
+        let mut value: *const c_char = ptr::null();
+
+        unsafe {
+            if bindings::vips_image_get_string(self.image, name.as_ptr(), &mut value) != 0 || value.is_null() {
+                vips_error_clear();
+                return None;
+            }
+
+            Some(CStr::from_ptr(value).to_string_lossy().into_owned())
+        }
+    }
+
+    pub fn get_blob(&self, name: &str) -> Option<Vec<u8>> {
+        let name = CString::new(name).ok()?;
+        let mut data: *const c_void = ptr::null();
+        let mut length: bindings::size_t = 0;
+
+        unsafe {
+            if bindings::vips_image_get_blob(self.image, name.as_ptr(), &mut data, &mut length) != 0 || data.is_null() {
+                vips_error_clear();
+                return None;
+            }
+
+            Some(slice::from_raw_parts(data as *const u8, length as usize).to_vec())
+        }
+    }
+
+    pub fn set_string(&mut self, name: &str, value: &str) {
+        let (name, value) = match (CString::new(name), CString::new(value)) {
+            (Ok(name), Ok(value)) => (name, value),
+            _ => return
+        };
+
+        unsafe {
+            bindings::vips_image_set_string(self.image, name.as_ptr(), value.as_ptr());
+        }
+    }
+
+    pub fn set_blob(&mut self, name: &str, data: &[u8]) {
+        let name = match CString::new(name) {
+            Ok(name) => name,
+            Err(_) => return
+        };
+
+        unsafe {
+            bindings::vips_image_set_blob_copy(self.image, name.as_ptr(), data.as_ptr() as *const c_void, data.len() as bindings::size_t);
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        let name = match CString::new(name) {
+            Ok(name) => name,
+            Err(_) => return
+        };
+
+        unsafe {
+            bindings::vips_image_remove(self.image, name.as_ptr());
+        }
+    }
+
+    // Pixels of the image with this metadata, the image must have the bands and format of the image the header was made from
+    pub fn apply(&self, image: &VipsImage) -> Option<VipsImage> {
+        let mut data: *mut c_void = ptr::null_mut();
+        let mut length: bindings::size_t = 0;
+
+        let buffer = unsafe {
+            if bindings::vips_image_write_to_buffer(self.image, HEADER_FORMAT.as_ptr(), &mut data, &mut length, ptr::null::<c_char>()) != 0 || data.is_null() {
+                vips_error_clear();
+                return None;
+            }
+
+            let buffer = slice::from_raw_parts(data as *const u8, length as usize).to_vec();
+            bindings::g_free(data);
+            buffer
+        };
+
+        // Header is copied to memory, so that it does not refer to the buffer
+        let header = VipsImage::image_copy_memory(VipsImage::new_from_buffer(&buffer, "").ok()?).ok()?;
+
+        // Output of embed and insert takes metadata of the first input
+        let canvas = ops::embed(&header, 0, 0, image.get_width(), image.get_height()).ok()?;
+        ops::insert(&canvas, image, 0, 0).ok()
+    }
+
+}
+
+impl Drop for ImageHeader {
+    fn drop(&mut self) {
+        unsafe { bindings::g_object_unref(self.image as bindings::gpointer) }
+    }
+}
+
+pub fn get_blob(image: &VipsImage, name: &str) -> Option<Vec<u8>> {
+    ImageHeader::new(image)?.get_blob(name)
+}
+
+// Name of the libvips loader able to load the file, e.g. VipsForeignLoadJpegFile
+pub fn find_loader(path: &str) -> Option<String> {
+    let path = CString::new(path).ok()?;
+
+    unsafe {
+        let loader = bindings::vips_foreign_find_load(path.as_ptr());
+
+        if loader.is_null() {
+            vips_error_clear();
+            return None;
+        }
+
+        Some(CStr::from_ptr(loader).to_string_lossy().into_owned())
+    }
+}
+
+// Check whether libvips was built with the operation, e.g. has_operation("VipsForeignLoad", "jxlload")
+pub fn has_operation(base: &str, nickname: &str) -> bool {
+    let (base, nickname) = match (CString::new(base), CString::new(nickname)) {
+        (Ok(base), Ok(nickname)) => (base, nickname),
+        _ => return false
+    };
+
+    unsafe { bindings::vips_type_find(base.as_ptr(), nickname.as_ptr()) != 0 }
+}
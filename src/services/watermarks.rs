@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use crate::parameters::Watermark;
+
+static WATERMARKS: OnceLock<Result<WatermarksConfig, String>> = OnceLock::new();
+
+// Paths map to watermarks in URL parameter format, applied to every image under the path
+#[derive(Default, Debug, Deserialize)]
+#[serde(default)]
+struct WatermarksFile {
+    paths: HashMap<String, String>
+}
+
+// Path prefixes are stored normalized, entries are validated when loading
+#[derive(Default, Debug)]
+pub struct WatermarksConfig {
+    paths: Vec<(PathBuf, Watermark)>
+}
+
+impl WatermarksConfig {
+
+    fn parse(content: &str) -> Result<WatermarksConfig, String> {
+
+        let file: WatermarksFile = serde_json::from_str(content).map_err(|e| format!("Failed to parse watermarks: {e}"))?;
+        let mut paths = Vec::with_capacity(file.paths.len());
+
+        for (prefix, value) in file.paths {
+            let watermark = match Watermark::from(&Some(value.clone())) {
+                Some(watermark) => watermark,
+                None => return Err(format!("Invalid mandatory watermark for path {prefix}: {value}"))
+            };
+
+            paths.push((normalize(Path::new(&prefix)), watermark));
+        }
+
+        Ok(WatermarksConfig { paths })
+
+    }
+
+    // Mandatory watermark of the longest prefix matching whole path components
+    fn get(&self, path: &Path) -> Option<&Watermark> {
+
+        let path = normalize(path);
+
+        self.paths.iter()
+            .filter(|(prefix, _)| path.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.components().count())
+            .map(|(_, watermark)| watermark)
+
+    }
+
+}
+
+// Unreadable or invalid configuration must not disable mandatory watermarks
+fn load() -> Result<WatermarksConfig, String> {
+
+    let path = match env::var("WATERMARKS") {
+        Ok(path) if !path.is_empty() => path,
+        _ => return Ok(WatermarksConfig::default())
+    };
+
+    let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read watermarks from {path}: {e}"))?;
+    let config = WatermarksConfig::parse(&content)?;
+
+    for (prefix, watermark) in &config.paths {
+        if !get_path(watermark).is_file() {
+            return Err(format!("Mandatory watermark for path {prefix:?} not found: {:?}", get_path(watermark)));
+        }
+    }
+
+    Ok(config)
+
+}
+
+// Called on startup, server refuses to start when mandatory watermarks cannot be loaded
+pub fn init() -> Result<(), String> {
+    WATERMARKS.get_or_init(load).as_ref().map(|_| ()).map_err(|e| e.clone())
+}
+
+pub fn get_mandatory(path: &Path) -> Option<Watermark> {
+    match WATERMARKS.get_or_init(load) {
+        Ok(config) => config.get(path).cloned(),
+        Err(_) => None
+    }
+}
+
+pub fn get_path(watermark: &Watermark) -> PathBuf {
+    Path::new(&env::var("WATERMARK_DIR").unwrap_or("watermarks".to_string())).join(&watermark.image)
+}
+
+// Relative path without `.` and `..` components, e.g. ./data/../data/previews/ -> data/previews
+fn normalize(path: &Path) -> PathBuf {
+
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => normalized.push(part),
+            Component::ParentDir => { normalized.pop(); },
+            _ => {}
+        }
+    }
+
+    normalized
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_config() -> WatermarksConfig {
+        WatermarksConfig::parse(r#"{"paths": {"data/previews/": "logo.png", "./data/previews/private": "private.png,top-left"}}"#).unwrap()
+    }
+
+    fn get_image(config: &WatermarksConfig, path: &str) -> Option<String> {
+        config.get(Path::new(path)).map(|watermark| watermark.image.clone())
+    }
+
+    #[test]
+    fn test_watermarks_get() {
+        let config = get_config();
+
+        assert_eq!(get_image(&config, "data/previews/x.jpg"), Some("logo.png".to_string()));
+        assert_eq!(get_image(&config, "./data/previews/x.jpg"), Some("logo.png".to_string()));
+        assert_eq!(get_image(&config, "data//previews/./x.jpg"), Some("logo.png".to_string()));
+        assert_eq!(get_image(&config, "data/public/../previews/x.jpg"), Some("logo.png".to_string()));
+        assert_eq!(get_image(&config, "data/previews/private/x.jpg"), Some("private.png".to_string()));
+        assert_eq!(get_image(&config, "data/previews2/x.jpg"), None);
+        assert_eq!(get_image(&config, "data/preview.jpg"), None);
+        assert_eq!(get_image(&config, "x.jpg"), None);
+    }
+
+    #[test]
+    fn test_watermarks_invalid() {
+        assert!(WatermarksConfig::parse("").is_err());
+        assert!(WatermarksConfig::parse(r#"{"paths": ["data/"]}"#).is_err());
+        assert!(WatermarksConfig::parse(r#"{"paths": {"data/": "../logo.png"}}"#).is_err());
+        assert!(WatermarksConfig::parse(r#"{"paths": {}}"#).is_ok());
+    }
+
+}